@@ -0,0 +1,210 @@
+use std::fmt;
+
+// Nametable arrangement wired on the cartridge
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower, // Every nametable maps to the first 1KB
+    SingleScreenUpper, // Every nametable maps to the second 1KB
+    FourScreen,
+}
+
+// Reasons an iNES image can be rejected
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CartError {
+    BadMagic,                                    // Does not start with "NES\x1A"
+    NoPrgRom,                                    // The header gives no PRG ROM
+    Truncated { expected: usize, found: usize }, // Shorter than the header says
+    UnsupportedMapper(u8),                       // No emulation for this mapper number
+}
+
+impl fmt::Display for CartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartError::BadMagic => write!(f, "not an iNES image (bad magic number)"),
+            CartError::NoPrgRom => write!(f, "iNES image has no PRG ROM"),
+            CartError::Truncated { expected, found } => write!(
+                f,
+                "truncated iNES image: expected {} bytes, found {}",
+                expected, found
+            ),
+            CartError::UnsupportedMapper(id) => write!(f, "mapper {} is not supported", id),
+        }
+    }
+}
+
+impl std::error::Error for CartError {}
+
+// A game cartridge loaded from an iNES (.nes) image
+pub struct Cartridge {
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,    // PRG RAM is battery backed
+    pub prg_rom: Vec<u8>, // Program ROM, multiple of 16KB
+    pub prg_ram: Vec<u8>, // 8KB of PRG RAM at $6000-$7FFF
+    pub chr: Vec<u8>,     // Character ROM, multiple of 8KB, or 8KB of CHR RAM
+    pub chr_ram: bool,    // The board has CHR RAM instead of CHR ROM
+    pub vram: Vec<u8>,    // Extra 2KB of nametable RAM on four-screen boards
+}
+
+impl Cartridge {
+    // Parse an iNES image.
+    // Header layout:
+    // 0-3: "NES\x1A"
+    // 4:   PRG ROM size in 16KB units
+    // 5:   CHR ROM size in 8KB units
+    // 6:   Flags 6 - low mapper nibble, four-screen, trainer, battery, mirroring
+    // 7:   Flags 7 - high mapper nibble
+    // 8-15 are not used here
+    // The header is followed by an optional 512 byte trainer,
+    // then PRG ROM and then CHR ROM.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CartError> {
+        if data.len() < 16 {
+            return Err(CartError::Truncated { expected: 16, found: data.len() });
+        }
+
+        if data[0..4] != *b"NES\x1A" {
+            return Err(CartError::BadMagic);
+        }
+
+        if data[4] == 0 {
+            return Err(CartError::NoPrgRom);
+        }
+
+        let prg_size = data[4] as usize * 0x4000;
+        let chr_size = data[5] as usize * 0x2000;
+        let flags_6 = data[6];
+        let flags_7 = data[7];
+
+        let mapper = (flags_7 & 0xF0) | (flags_6 >> 4);
+
+        let mirroring = if flags_6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags_6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        let battery = flags_6 & 0x02 != 0;
+
+        // Skip the trainer, it is never used by the mappers supported here
+        let prg_start = if flags_6 & 0x04 != 0 { 16 + 512 } else { 16 };
+        let chr_start = prg_start + prg_size;
+        let end = chr_start + chr_size;
+
+        if data.len() < end {
+            return Err(CartError::Truncated { expected: end, found: data.len() });
+        }
+
+        // Boards without CHR ROM have 8KB of CHR RAM instead
+        let chr_ram = chr_size == 0;
+
+        Ok(Self {
+            mapper,
+            mirroring,
+            battery,
+            prg_rom: data[prg_start..chr_start].to_vec(),
+            prg_ram: vec![0; 0x2000],
+            chr: if chr_ram { vec![0; 0x2000] } else { data[chr_start..end].to_vec() },
+            chr_ram,
+            vram: if mirroring == Mirroring::FourScreen { vec![0; 0x0800] } else { Vec::new() },
+        })
+    }
+
+    // Contents of the PRG RAM, for a frontend to persist battery saves
+    pub fn save_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    // Restore PRG RAM saved earlier with save_ram
+    pub fn load_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.prg_ram.len());
+        self.prg_ram[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An iNES image with the given header bytes 4-7 and the PRG and CHR
+    // banks filled with their bank number + 1
+    fn image(header: [u8; 4], trainer: bool) -> Vec<u8> {
+        let mut data = b"NES\x1A".to_vec();
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&[0; 8]);
+
+        if trainer {
+            data.extend_from_slice(&[0xEE; 512]);
+        }
+        for bank in 0..header[0] {
+            data.extend_from_slice(&[bank + 1; 0x4000]);
+        }
+        for bank in 0..header[1] {
+            data.extend_from_slice(&[0x80 + bank + 1; 0x2000]);
+        }
+
+        data
+    }
+
+    #[test]
+    fn parses_the_header() {
+        // 2 PRG banks, 1 CHR bank, mapper $12, vertical, battery, trainer
+        let cart = Cartridge::from_bytes(&image([2, 1, 0x27, 0x10], true)).unwrap();
+
+        assert_eq!(cart.mapper, 0x12);
+        assert_eq!(cart.mirroring, Mirroring::Vertical);
+        assert!(cart.battery);
+        assert_eq!(cart.prg_rom.len(), 0x8000);
+        assert_eq!(cart.prg_rom[0], 0x01);
+        assert_eq!(cart.prg_rom[0x7FFF], 0x02);
+        assert_eq!(cart.chr, vec![0x81; 0x2000]);
+        assert!(!cart.chr_ram);
+    }
+
+    #[test]
+    fn no_chr_rom_means_chr_ram() {
+        let cart = Cartridge::from_bytes(&image([1, 0, 0x08, 0x00], false)).unwrap();
+
+        assert_eq!(cart.mapper, 0);
+        assert_eq!(cart.mirroring, Mirroring::FourScreen);
+        assert!(!cart.battery);
+        assert!(cart.chr_ram);
+        assert_eq!(cart.chr.len(), 0x2000);
+        assert_eq!(cart.vram.len(), 0x0800);
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        assert_eq!(
+            Cartridge::from_bytes(b"NES").err(),
+            Some(CartError::Truncated { expected: 16, found: 3 })
+        );
+
+        let mut data = image([1, 1, 0, 0], false);
+        data[0] = b'M';
+        assert_eq!(Cartridge::from_bytes(&data).err(), Some(CartError::BadMagic));
+
+        let data = image([1, 1, 0, 0], false);
+        assert_eq!(
+            Cartridge::from_bytes(&data[..data.len() - 1]).err(),
+            Some(CartError::Truncated { expected: 0x6010, found: 0x600F })
+        );
+
+        assert_eq!(
+            Cartridge::from_bytes(&image([0, 1, 0, 0], false)).err(),
+            Some(CartError::NoPrgRom)
+        );
+    }
+
+    #[test]
+    fn error_display() {
+        assert_eq!(CartError::NoPrgRom.to_string(), "iNES image has no PRG ROM");
+        assert_eq!(
+            CartError::Truncated { expected: 16, found: 3 }.to_string(),
+            "truncated iNES image: expected 16 bytes, found 3"
+        );
+    }
+}
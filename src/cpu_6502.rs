@@ -3,7 +3,8 @@
 // since PC is incremented when the relative address byte is read.
 
 use crate::cpu_bus;
-use cpu_bus::Bus;
+use cpu_bus::CpuBus;
+use crate::error::EmuError;
 
 // Struct of the NES CPU (MOS 6502)
 pub struct MOS6502 {
@@ -12,9 +13,26 @@ pub struct MOS6502 {
     y: u8,         // Y Register
     s: u8,         // Status Register
     pc: u16,       // Program Counter
-    sp: i16,       // Stack Pointer
-    clk: u32,      // Additional Clock Cycles
-    acc_addr: bool // Set when Accumulator addressing occurs
+    sp: u8,        // Stack Pointer
+    clk: u64,      // Elapsed Clock Cycles
+    jammed: bool,  // Set when the CPU has halted
+    decimal: bool, // ADC and SBC honour the Decimal flag (not on the NES)
+    nmi_pending: bool, // NMI requested and not yet serviced
+    nmi_line: bool,    // Level of the NMI line at the last poll
+    irq_pending: bool, // Level of the IRQ line
+    take_nmi: bool,    // NMI recognised when polling at the end of the last instruction
+    take_irq: bool,    // IRQ recognised when polling at the end of the last instruction
+    unstable: UnstableMode, // Behaviour of the unstable unofficial opcodes
+    error: Option<EmuError>, // Raised by the last step, reported by try_step()
+}
+
+// How the hardware-unstable unofficial opcodes
+// (XAA, LXA, AHX, TAS, LAS, SHX, SHY) are emulated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnstableMode {
+    Common,     // Commonly accepted behaviour including the magic constants
+    TreatAsNop, // Consume the operand and do nothing
+    Error,      // Stop in front of the opcode and report it
 }
 
 enum Flags {
@@ -23,24 +41,458 @@ enum Flags {
     Interrupt = 0x01 << 2, // Interrupt Disable Flag
     Decimal   = 0x01 << 3, // Decimal Mode Flag (Unused in NES)
     Break     = 0x01 << 4, // Break Flag
+    Unused    = 0x01 << 5, // Unused Flag (Always set when pushed)
     Overflow  = 0x01 << 6, // Overflow Flag
     Negative  = 0x01 << 7  // Negative Flag
 }
 
-// Struct used for returning the result from
-// addressing mode functions
-struct AddrRes {
-    addr: u16,
-    cycle: bool,
+// Operand of an instruction, as resolved from its addressing mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Operand {
+    Implied,
+    Accumulator,
+    Immediate(u8),
+    Relative(u16),      // Sign extended branch offset
+    Address(u16, bool), // Effective address, set if indexing crossed a page
+}
+
+impl Operand {
+    // The effective address or the branch offset
+    fn addr(self) -> u16 {
+        match self {
+            Self::Address(addr, _) | Self::Relative(addr) => addr,
+            _ => 0,
+        }
+    }
+
+    // Set if indexing crossed a page boundary
+    fn page_crossed(self) -> bool {
+        matches!(self, Self::Address(_, true))
+    }
+}
+
+// Handler of an opcode. Returns the clock cycles taken on top of the
+// base cycles of its table entry, which only taken branches need.
+// The extra cycle of an indexed access crossing a page is added by
+// step() for the entries marked with page_penalty, the reads. Stores and
+// read-modify-write instructions always pay for the fix-up of the high
+// byte in their base cycles, so they never take it.
+type Opcode = fn(&mut MOS6502, &mut dyn CpuBus, Operand) -> u8;
+
+// Addressing modes of the 6502
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    Relative,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndexedIndirect, // (Indirect,X)
+    IndirectIndexed, // (Indirect),Y
+}
+
+impl AddressingMode {
+    // Length in bytes of an instruction using this mode, opcode included
+    const fn len(self) -> u8 {
+        match self {
+            Self::Implied | Self::Accumulator => 1,
+            Self::Immediate
+            | Self::Relative
+            | Self::ZeroPage
+            | Self::ZeroPageX
+            | Self::ZeroPageY
+            | Self::IndexedIndirect
+            | Self::IndirectIndexed => 2,
+            Self::Absolute | Self::AbsoluteX | Self::AbsoluteY | Self::Indirect => 3,
+        }
+    }
+}
+
+// Static information about an opcode.
+// Also used as the entry of the opcode dispatch table.
+#[derive(Clone, Copy)]
+pub struct OpcodeInfo {
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    pub len: u8,            // Instruction length in bytes
+    pub cycles: u8,         // Base clock cycles
+    pub page_penalty: bool, // Can take an extra cycle when crossing a page
+    op: Opcode,
 }
 
-impl AddrRes {
-    pub fn new(addr: u16, cycle: bool) -> Self {
-        Self { addr, cycle }
+impl OpcodeInfo {
+    const fn new(mnemonic: &'static str, op: Opcode, mode: AddressingMode, cycles: u8, page_penalty: bool) -> Self {
+        Self {
+            mnemonic,
+            mode,
+            len: mode.len(),
+            cycles,
+            page_penalty,
+            op,
+        }
     }
 }
 
-type AddrMode = fn(&mut MOS6502, &mut Bus) -> AddrRes;
+// Opcode table, indexed by the opcode byte.
+// Every opcode has an entry, the 2 cycle NOP placeholder
+// only fills the table while it is being built.
+pub const OPCODE_TABLE: [OpcodeInfo; 256] = {
+    type C = MOS6502;
+    type M = AddressingMode;
+    let mut t = [OpcodeInfo::new("???", C::opcode_xxx, M::Implied, 2, false); 256];
+
+    // ADC
+    t[0x69] = OpcodeInfo::new("ADC", C::opcode_adc, M::Immediate, 2, false);
+    t[0x65] = OpcodeInfo::new("ADC", C::opcode_adc, M::ZeroPage, 3, false);
+    t[0x75] = OpcodeInfo::new("ADC", C::opcode_adc, M::ZeroPageX, 4, false);
+    t[0x6D] = OpcodeInfo::new("ADC", C::opcode_adc, M::Absolute, 4, false);
+    t[0x7D] = OpcodeInfo::new("ADC", C::opcode_adc, M::AbsoluteX, 4, true);
+    t[0x79] = OpcodeInfo::new("ADC", C::opcode_adc, M::AbsoluteY, 4, true);
+    t[0x61] = OpcodeInfo::new("ADC", C::opcode_adc, M::IndexedIndirect, 6, false);
+    t[0x71] = OpcodeInfo::new("ADC", C::opcode_adc, M::IndirectIndexed, 5, true);
+
+    // AND
+    t[0x29] = OpcodeInfo::new("AND", C::opcode_and, M::Immediate, 2, false);
+    t[0x25] = OpcodeInfo::new("AND", C::opcode_and, M::ZeroPage, 3, false);
+    t[0x35] = OpcodeInfo::new("AND", C::opcode_and, M::ZeroPageX, 4, false);
+    t[0x2D] = OpcodeInfo::new("AND", C::opcode_and, M::Absolute, 4, false);
+    t[0x3D] = OpcodeInfo::new("AND", C::opcode_and, M::AbsoluteX, 4, true);
+    t[0x39] = OpcodeInfo::new("AND", C::opcode_and, M::AbsoluteY, 4, true);
+    t[0x21] = OpcodeInfo::new("AND", C::opcode_and, M::IndexedIndirect, 6, false);
+    t[0x31] = OpcodeInfo::new("AND", C::opcode_and, M::IndirectIndexed, 5, true);
+
+    // ASL
+    t[0x0A] = OpcodeInfo::new("ASL", C::opcode_asl, M::Accumulator, 2, false);
+    t[0x06] = OpcodeInfo::new("ASL", C::opcode_asl, M::ZeroPage, 5, false);
+    t[0x16] = OpcodeInfo::new("ASL", C::opcode_asl, M::ZeroPageX, 6, false);
+    t[0x0E] = OpcodeInfo::new("ASL", C::opcode_asl, M::Absolute, 6, false);
+    t[0x1E] = OpcodeInfo::new("ASL", C::opcode_asl, M::AbsoluteX, 7, false);
+
+    // Branches
+    t[0x90] = OpcodeInfo::new("BCC", C::opcode_bcc, M::Relative, 2, true);
+    t[0xB0] = OpcodeInfo::new("BCS", C::opcode_bcs, M::Relative, 2, true);
+    t[0xF0] = OpcodeInfo::new("BEQ", C::opcode_beq, M::Relative, 2, true);
+    t[0x30] = OpcodeInfo::new("BMI", C::opcode_bmi, M::Relative, 2, true);
+    t[0xD0] = OpcodeInfo::new("BNE", C::opcode_bne, M::Relative, 2, true);
+    t[0x10] = OpcodeInfo::new("BPL", C::opcode_bpl, M::Relative, 2, true);
+    t[0x50] = OpcodeInfo::new("BVC", C::opcode_bvc, M::Relative, 2, true);
+    t[0x70] = OpcodeInfo::new("BVS", C::opcode_bvs, M::Relative, 2, true);
+
+    // BIT
+    t[0x24] = OpcodeInfo::new("BIT", C::opcode_bit, M::ZeroPage, 3, false);
+    t[0x2C] = OpcodeInfo::new("BIT", C::opcode_bit, M::Absolute, 4, false);
+
+    // LDA
+    t[0xA9] = OpcodeInfo::new("LDA", C::opcode_lda, M::Immediate, 2, false);
+    t[0xA5] = OpcodeInfo::new("LDA", C::opcode_lda, M::ZeroPage, 3, false);
+    t[0xB5] = OpcodeInfo::new("LDA", C::opcode_lda, M::ZeroPageX, 4, false);
+    t[0xAD] = OpcodeInfo::new("LDA", C::opcode_lda, M::Absolute, 4, false);
+    t[0xBD] = OpcodeInfo::new("LDA", C::opcode_lda, M::AbsoluteX, 4, true);
+    t[0xB9] = OpcodeInfo::new("LDA", C::opcode_lda, M::AbsoluteY, 4, true);
+    t[0xA1] = OpcodeInfo::new("LDA", C::opcode_lda, M::IndexedIndirect, 6, false);
+    t[0xB1] = OpcodeInfo::new("LDA", C::opcode_lda, M::IndirectIndexed, 5, true);
+
+    // LDX
+    t[0xA2] = OpcodeInfo::new("LDX", C::opcode_ldx, M::Immediate, 2, false);
+    t[0xA6] = OpcodeInfo::new("LDX", C::opcode_ldx, M::ZeroPage, 3, false);
+    t[0xB6] = OpcodeInfo::new("LDX", C::opcode_ldx, M::ZeroPageY, 4, false);
+    t[0xAE] = OpcodeInfo::new("LDX", C::opcode_ldx, M::Absolute, 4, false);
+    t[0xBE] = OpcodeInfo::new("LDX", C::opcode_ldx, M::AbsoluteY, 4, true);
+
+    // LDY
+    t[0xA0] = OpcodeInfo::new("LDY", C::opcode_ldy, M::Immediate, 2, false);
+    t[0xA4] = OpcodeInfo::new("LDY", C::opcode_ldy, M::ZeroPage, 3, false);
+    t[0xB4] = OpcodeInfo::new("LDY", C::opcode_ldy, M::ZeroPageX, 4, false);
+    t[0xAC] = OpcodeInfo::new("LDY", C::opcode_ldy, M::Absolute, 4, false);
+    t[0xBC] = OpcodeInfo::new("LDY", C::opcode_ldy, M::AbsoluteX, 4, true);
+
+    // STA
+    t[0x85] = OpcodeInfo::new("STA", C::opcode_sta, M::ZeroPage, 3, false);
+    t[0x95] = OpcodeInfo::new("STA", C::opcode_sta, M::ZeroPageX, 4, false);
+    t[0x8D] = OpcodeInfo::new("STA", C::opcode_sta, M::Absolute, 4, false);
+    t[0x9D] = OpcodeInfo::new("STA", C::opcode_sta, M::AbsoluteX, 5, false);
+    t[0x99] = OpcodeInfo::new("STA", C::opcode_sta, M::AbsoluteY, 5, false);
+    t[0x81] = OpcodeInfo::new("STA", C::opcode_sta, M::IndexedIndirect, 6, false);
+    t[0x91] = OpcodeInfo::new("STA", C::opcode_sta, M::IndirectIndexed, 6, false);
+
+    // STX
+    t[0x86] = OpcodeInfo::new("STX", C::opcode_stx, M::ZeroPage, 3, false);
+    t[0x96] = OpcodeInfo::new("STX", C::opcode_stx, M::ZeroPageY, 4, false);
+    t[0x8E] = OpcodeInfo::new("STX", C::opcode_stx, M::Absolute, 4, false);
+
+    // STY
+    t[0x84] = OpcodeInfo::new("STY", C::opcode_sty, M::ZeroPage, 3, false);
+    t[0x94] = OpcodeInfo::new("STY", C::opcode_sty, M::ZeroPageX, 4, false);
+    t[0x8C] = OpcodeInfo::new("STY", C::opcode_sty, M::Absolute, 4, false);
+
+    // Register transfers
+    t[0xAA] = OpcodeInfo::new("TAX", C::opcode_tax, M::Implied, 2, false);
+    t[0xA8] = OpcodeInfo::new("TAY", C::opcode_tay, M::Implied, 2, false);
+    t[0x8A] = OpcodeInfo::new("TXA", C::opcode_txa, M::Implied, 2, false);
+    t[0x98] = OpcodeInfo::new("TYA", C::opcode_tya, M::Implied, 2, false);
+    t[0xBA] = OpcodeInfo::new("TSX", C::opcode_tsx, M::Implied, 2, false);
+    t[0x9A] = OpcodeInfo::new("TXS", C::opcode_txs, M::Implied, 2, false);
+
+    // Stack operations
+    t[0x48] = OpcodeInfo::new("PHA", C::opcode_pha, M::Implied, 3, false);
+    t[0x08] = OpcodeInfo::new("PHP", C::opcode_php, M::Implied, 3, false);
+    t[0x68] = OpcodeInfo::new("PLA", C::opcode_pla, M::Implied, 4, false);
+    t[0x28] = OpcodeInfo::new("PLP", C::opcode_plp, M::Implied, 4, false);
+
+    // Jumps
+    t[0x4C] = OpcodeInfo::new("JMP", C::opcode_jmp, M::Absolute, 3, false);
+    t[0x6C] = OpcodeInfo::new("JMP", C::opcode_jmp, M::Indirect, 5, false);
+    t[0x20] = OpcodeInfo::new("JSR", C::opcode_jsr, M::Absolute, 6, false);
+    t[0x60] = OpcodeInfo::new("RTS", C::opcode_rts, M::Implied, 6, false);
+
+    // Interrupts
+    t[0x00] = OpcodeInfo::new("BRK", C::opcode_brk, M::Implied, 7, false);
+    t[0x40] = OpcodeInfo::new("RTI", C::opcode_rti, M::Implied, 6, false);
+
+    // SBC
+    t[0xE9] = OpcodeInfo::new("SBC", C::opcode_sbc, M::Immediate, 2, false);
+    t[0xE5] = OpcodeInfo::new("SBC", C::opcode_sbc, M::ZeroPage, 3, false);
+    t[0xF5] = OpcodeInfo::new("SBC", C::opcode_sbc, M::ZeroPageX, 4, false);
+    t[0xED] = OpcodeInfo::new("SBC", C::opcode_sbc, M::Absolute, 4, false);
+    t[0xFD] = OpcodeInfo::new("SBC", C::opcode_sbc, M::AbsoluteX, 4, true);
+    t[0xF9] = OpcodeInfo::new("SBC", C::opcode_sbc, M::AbsoluteY, 4, true);
+    t[0xE1] = OpcodeInfo::new("SBC", C::opcode_sbc, M::IndexedIndirect, 6, false);
+    t[0xF1] = OpcodeInfo::new("SBC", C::opcode_sbc, M::IndirectIndexed, 5, true);
+    t[0xEB] = OpcodeInfo::new("SBC", C::opcode_sbc, M::Immediate, 2, false);
+
+    // CMP
+    t[0xC9] = OpcodeInfo::new("CMP", C::opcode_cmp, M::Immediate, 2, false);
+    t[0xC5] = OpcodeInfo::new("CMP", C::opcode_cmp, M::ZeroPage, 3, false);
+    t[0xD5] = OpcodeInfo::new("CMP", C::opcode_cmp, M::ZeroPageX, 4, false);
+    t[0xCD] = OpcodeInfo::new("CMP", C::opcode_cmp, M::Absolute, 4, false);
+    t[0xDD] = OpcodeInfo::new("CMP", C::opcode_cmp, M::AbsoluteX, 4, true);
+    t[0xD9] = OpcodeInfo::new("CMP", C::opcode_cmp, M::AbsoluteY, 4, true);
+    t[0xC1] = OpcodeInfo::new("CMP", C::opcode_cmp, M::IndexedIndirect, 6, false);
+    t[0xD1] = OpcodeInfo::new("CMP", C::opcode_cmp, M::IndirectIndexed, 5, true);
+
+    // CPX
+    t[0xE0] = OpcodeInfo::new("CPX", C::opcode_cpx, M::Immediate, 2, false);
+    t[0xE4] = OpcodeInfo::new("CPX", C::opcode_cpx, M::ZeroPage, 3, false);
+    t[0xEC] = OpcodeInfo::new("CPX", C::opcode_cpx, M::Absolute, 4, false);
+
+    // CPY
+    t[0xC0] = OpcodeInfo::new("CPY", C::opcode_cpy, M::Immediate, 2, false);
+    t[0xC4] = OpcodeInfo::new("CPY", C::opcode_cpy, M::ZeroPage, 3, false);
+    t[0xCC] = OpcodeInfo::new("CPY", C::opcode_cpy, M::Absolute, 4, false);
+
+    // DEC
+    t[0xC6] = OpcodeInfo::new("DEC", C::opcode_dec, M::ZeroPage, 5, false);
+    t[0xD6] = OpcodeInfo::new("DEC", C::opcode_dec, M::ZeroPageX, 6, false);
+    t[0xCE] = OpcodeInfo::new("DEC", C::opcode_dec, M::Absolute, 6, false);
+    t[0xDE] = OpcodeInfo::new("DEC", C::opcode_dec, M::AbsoluteX, 7, false);
+
+    // INC
+    t[0xE6] = OpcodeInfo::new("INC", C::opcode_inc, M::ZeroPage, 5, false);
+    t[0xF6] = OpcodeInfo::new("INC", C::opcode_inc, M::ZeroPageX, 6, false);
+    t[0xEE] = OpcodeInfo::new("INC", C::opcode_inc, M::Absolute, 6, false);
+    t[0xFE] = OpcodeInfo::new("INC", C::opcode_inc, M::AbsoluteX, 7, false);
+
+    // Register increments and decrements
+    t[0xCA] = OpcodeInfo::new("DEX", C::opcode_dex, M::Implied, 2, false);
+    t[0x88] = OpcodeInfo::new("DEY", C::opcode_dey, M::Implied, 2, false);
+    t[0xE8] = OpcodeInfo::new("INX", C::opcode_inx, M::Implied, 2, false);
+    t[0xC8] = OpcodeInfo::new("INY", C::opcode_iny, M::Implied, 2, false);
+
+    // LSR
+    t[0x4A] = OpcodeInfo::new("LSR", C::opcode_lsr, M::Accumulator, 2, false);
+    t[0x46] = OpcodeInfo::new("LSR", C::opcode_lsr, M::ZeroPage, 5, false);
+    t[0x56] = OpcodeInfo::new("LSR", C::opcode_lsr, M::ZeroPageX, 6, false);
+    t[0x4E] = OpcodeInfo::new("LSR", C::opcode_lsr, M::Absolute, 6, false);
+    t[0x5E] = OpcodeInfo::new("LSR", C::opcode_lsr, M::AbsoluteX, 7, false);
+
+    // ROL
+    t[0x2A] = OpcodeInfo::new("ROL", C::opcode_rol, M::Accumulator, 2, false);
+    t[0x26] = OpcodeInfo::new("ROL", C::opcode_rol, M::ZeroPage, 5, false);
+    t[0x36] = OpcodeInfo::new("ROL", C::opcode_rol, M::ZeroPageX, 6, false);
+    t[0x2E] = OpcodeInfo::new("ROL", C::opcode_rol, M::Absolute, 6, false);
+    t[0x3E] = OpcodeInfo::new("ROL", C::opcode_rol, M::AbsoluteX, 7, false);
+
+    // ROR
+    t[0x6A] = OpcodeInfo::new("ROR", C::opcode_ror, M::Accumulator, 2, false);
+    t[0x66] = OpcodeInfo::new("ROR", C::opcode_ror, M::ZeroPage, 5, false);
+    t[0x76] = OpcodeInfo::new("ROR", C::opcode_ror, M::ZeroPageX, 6, false);
+    t[0x6E] = OpcodeInfo::new("ROR", C::opcode_ror, M::Absolute, 6, false);
+    t[0x7E] = OpcodeInfo::new("ROR", C::opcode_ror, M::AbsoluteX, 7, false);
+
+    // ORA
+    t[0x09] = OpcodeInfo::new("ORA", C::opcode_ora, M::Immediate, 2, false);
+    t[0x05] = OpcodeInfo::new("ORA", C::opcode_ora, M::ZeroPage, 3, false);
+    t[0x15] = OpcodeInfo::new("ORA", C::opcode_ora, M::ZeroPageX, 4, false);
+    t[0x0D] = OpcodeInfo::new("ORA", C::opcode_ora, M::Absolute, 4, false);
+    t[0x1D] = OpcodeInfo::new("ORA", C::opcode_ora, M::AbsoluteX, 4, true);
+    t[0x19] = OpcodeInfo::new("ORA", C::opcode_ora, M::AbsoluteY, 4, true);
+    t[0x01] = OpcodeInfo::new("ORA", C::opcode_ora, M::IndexedIndirect, 6, false);
+    t[0x11] = OpcodeInfo::new("ORA", C::opcode_ora, M::IndirectIndexed, 5, true);
+
+    // EOR
+    t[0x49] = OpcodeInfo::new("EOR", C::opcode_eor, M::Immediate, 2, false);
+    t[0x45] = OpcodeInfo::new("EOR", C::opcode_eor, M::ZeroPage, 3, false);
+    t[0x55] = OpcodeInfo::new("EOR", C::opcode_eor, M::ZeroPageX, 4, false);
+    t[0x4D] = OpcodeInfo::new("EOR", C::opcode_eor, M::Absolute, 4, false);
+    t[0x5D] = OpcodeInfo::new("EOR", C::opcode_eor, M::AbsoluteX, 4, true);
+    t[0x59] = OpcodeInfo::new("EOR", C::opcode_eor, M::AbsoluteY, 4, true);
+    t[0x41] = OpcodeInfo::new("EOR", C::opcode_eor, M::IndexedIndirect, 6, false);
+    t[0x51] = OpcodeInfo::new("EOR", C::opcode_eor, M::IndirectIndexed, 5, true);
+
+    // Flag instructions
+    t[0x18] = OpcodeInfo::new("CLC", C::opcode_clc, M::Implied, 2, false);
+    t[0xD8] = OpcodeInfo::new("CLD", C::opcode_cld, M::Implied, 2, false);
+    t[0x58] = OpcodeInfo::new("CLI", C::opcode_cli, M::Implied, 2, false);
+    t[0xB8] = OpcodeInfo::new("CLV", C::opcode_clv, M::Implied, 2, false);
+    t[0x38] = OpcodeInfo::new("SEC", C::opcode_sec, M::Implied, 2, false);
+    t[0xF8] = OpcodeInfo::new("SED", C::opcode_sed, M::Implied, 2, false);
+    t[0x78] = OpcodeInfo::new("SEI", C::opcode_sei, M::Implied, 2, false);
+
+    // NOP
+    t[0xEA] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+
+    // Unofficial NOPs
+    t[0x1A] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+    t[0x3A] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+    t[0x5A] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+    t[0x7A] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+    t[0xDA] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+    t[0xFA] = OpcodeInfo::new("NOP", C::opcode_nop, M::Implied, 2, false);
+    t[0x80] = OpcodeInfo::new("NOP", C::opcode_ign, M::Immediate, 2, false);
+    t[0x82] = OpcodeInfo::new("NOP", C::opcode_ign, M::Immediate, 2, false);
+    t[0x89] = OpcodeInfo::new("NOP", C::opcode_ign, M::Immediate, 2, false);
+    t[0xC2] = OpcodeInfo::new("NOP", C::opcode_ign, M::Immediate, 2, false);
+    t[0xE2] = OpcodeInfo::new("NOP", C::opcode_ign, M::Immediate, 2, false);
+    t[0x04] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPage, 3, false);
+    t[0x44] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPage, 3, false);
+    t[0x64] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPage, 3, false);
+    t[0x14] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPageX, 4, false);
+    t[0x34] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPageX, 4, false);
+    t[0x54] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPageX, 4, false);
+    t[0x74] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPageX, 4, false);
+    t[0xD4] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPageX, 4, false);
+    t[0xF4] = OpcodeInfo::new("NOP", C::opcode_ign, M::ZeroPageX, 4, false);
+    t[0x0C] = OpcodeInfo::new("NOP", C::opcode_ign, M::Absolute, 4, false);
+    t[0x1C] = OpcodeInfo::new("NOP", C::opcode_ign, M::AbsoluteX, 4, true);
+    t[0x3C] = OpcodeInfo::new("NOP", C::opcode_ign, M::AbsoluteX, 4, true);
+    t[0x5C] = OpcodeInfo::new("NOP", C::opcode_ign, M::AbsoluteX, 4, true);
+    t[0x7C] = OpcodeInfo::new("NOP", C::opcode_ign, M::AbsoluteX, 4, true);
+    t[0xDC] = OpcodeInfo::new("NOP", C::opcode_ign, M::AbsoluteX, 4, true);
+    t[0xFC] = OpcodeInfo::new("NOP", C::opcode_ign, M::AbsoluteX, 4, true);
+
+    // Unofficial LAX
+    t[0xA7] = OpcodeInfo::new("LAX", C::opcode_lax, M::ZeroPage, 3, false);
+    t[0xB7] = OpcodeInfo::new("LAX", C::opcode_lax, M::ZeroPageY, 4, false);
+    t[0xAF] = OpcodeInfo::new("LAX", C::opcode_lax, M::Absolute, 4, false);
+    t[0xBF] = OpcodeInfo::new("LAX", C::opcode_lax, M::AbsoluteY, 4, true);
+    t[0xA3] = OpcodeInfo::new("LAX", C::opcode_lax, M::IndexedIndirect, 6, false);
+    t[0xB3] = OpcodeInfo::new("LAX", C::opcode_lax, M::IndirectIndexed, 5, true);
+
+    // Unofficial SAX
+    t[0x87] = OpcodeInfo::new("SAX", C::opcode_sax, M::ZeroPage, 3, false);
+    t[0x97] = OpcodeInfo::new("SAX", C::opcode_sax, M::ZeroPageY, 4, false);
+    t[0x8F] = OpcodeInfo::new("SAX", C::opcode_sax, M::Absolute, 4, false);
+    t[0x83] = OpcodeInfo::new("SAX", C::opcode_sax, M::IndexedIndirect, 6, false);
+
+    // Unofficial DCP
+    t[0xC7] = OpcodeInfo::new("DCP", C::opcode_dcp, M::ZeroPage, 5, false);
+    t[0xD7] = OpcodeInfo::new("DCP", C::opcode_dcp, M::ZeroPageX, 6, false);
+    t[0xCF] = OpcodeInfo::new("DCP", C::opcode_dcp, M::Absolute, 6, false);
+    t[0xDF] = OpcodeInfo::new("DCP", C::opcode_dcp, M::AbsoluteX, 7, false);
+    t[0xDB] = OpcodeInfo::new("DCP", C::opcode_dcp, M::AbsoluteY, 7, false);
+    t[0xC3] = OpcodeInfo::new("DCP", C::opcode_dcp, M::IndexedIndirect, 8, false);
+    t[0xD3] = OpcodeInfo::new("DCP", C::opcode_dcp, M::IndirectIndexed, 8, false);
+
+    // Unofficial ISC
+    t[0xE7] = OpcodeInfo::new("ISC", C::opcode_isc, M::ZeroPage, 5, false);
+    t[0xF7] = OpcodeInfo::new("ISC", C::opcode_isc, M::ZeroPageX, 6, false);
+    t[0xEF] = OpcodeInfo::new("ISC", C::opcode_isc, M::Absolute, 6, false);
+    t[0xFF] = OpcodeInfo::new("ISC", C::opcode_isc, M::AbsoluteX, 7, false);
+    t[0xFB] = OpcodeInfo::new("ISC", C::opcode_isc, M::AbsoluteY, 7, false);
+    t[0xE3] = OpcodeInfo::new("ISC", C::opcode_isc, M::IndexedIndirect, 8, false);
+    t[0xF3] = OpcodeInfo::new("ISC", C::opcode_isc, M::IndirectIndexed, 8, false);
+
+    // Unofficial RLA
+    t[0x27] = OpcodeInfo::new("RLA", C::opcode_rla, M::ZeroPage, 5, false);
+    t[0x37] = OpcodeInfo::new("RLA", C::opcode_rla, M::ZeroPageX, 6, false);
+    t[0x2F] = OpcodeInfo::new("RLA", C::opcode_rla, M::Absolute, 6, false);
+    t[0x3F] = OpcodeInfo::new("RLA", C::opcode_rla, M::AbsoluteX, 7, false);
+    t[0x3B] = OpcodeInfo::new("RLA", C::opcode_rla, M::AbsoluteY, 7, false);
+    t[0x23] = OpcodeInfo::new("RLA", C::opcode_rla, M::IndexedIndirect, 8, false);
+    t[0x33] = OpcodeInfo::new("RLA", C::opcode_rla, M::IndirectIndexed, 8, false);
+
+    // Unofficial RRA
+    t[0x67] = OpcodeInfo::new("RRA", C::opcode_rra, M::ZeroPage, 5, false);
+    t[0x77] = OpcodeInfo::new("RRA", C::opcode_rra, M::ZeroPageX, 6, false);
+    t[0x6F] = OpcodeInfo::new("RRA", C::opcode_rra, M::Absolute, 6, false);
+    t[0x7F] = OpcodeInfo::new("RRA", C::opcode_rra, M::AbsoluteX, 7, false);
+    t[0x7B] = OpcodeInfo::new("RRA", C::opcode_rra, M::AbsoluteY, 7, false);
+    t[0x63] = OpcodeInfo::new("RRA", C::opcode_rra, M::IndexedIndirect, 8, false);
+    t[0x73] = OpcodeInfo::new("RRA", C::opcode_rra, M::IndirectIndexed, 8, false);
+
+    // Unofficial SLO
+    t[0x07] = OpcodeInfo::new("SLO", C::opcode_slo, M::ZeroPage, 5, false);
+    t[0x17] = OpcodeInfo::new("SLO", C::opcode_slo, M::ZeroPageX, 6, false);
+    t[0x0F] = OpcodeInfo::new("SLO", C::opcode_slo, M::Absolute, 6, false);
+    t[0x1F] = OpcodeInfo::new("SLO", C::opcode_slo, M::AbsoluteX, 7, false);
+    t[0x1B] = OpcodeInfo::new("SLO", C::opcode_slo, M::AbsoluteY, 7, false);
+    t[0x03] = OpcodeInfo::new("SLO", C::opcode_slo, M::IndexedIndirect, 8, false);
+    t[0x13] = OpcodeInfo::new("SLO", C::opcode_slo, M::IndirectIndexed, 8, false);
+
+    // Unofficial SRE
+    t[0x47] = OpcodeInfo::new("SRE", C::opcode_sre, M::ZeroPage, 5, false);
+    t[0x57] = OpcodeInfo::new("SRE", C::opcode_sre, M::ZeroPageX, 6, false);
+    t[0x4F] = OpcodeInfo::new("SRE", C::opcode_sre, M::Absolute, 6, false);
+    t[0x5F] = OpcodeInfo::new("SRE", C::opcode_sre, M::AbsoluteX, 7, false);
+    t[0x5B] = OpcodeInfo::new("SRE", C::opcode_sre, M::AbsoluteY, 7, false);
+    t[0x43] = OpcodeInfo::new("SRE", C::opcode_sre, M::IndexedIndirect, 8, false);
+    t[0x53] = OpcodeInfo::new("SRE", C::opcode_sre, M::IndirectIndexed, 8, false);
+
+    // Unofficial immediate opcodes
+    t[0x0B] = OpcodeInfo::new("ANC", C::opcode_anc, M::Immediate, 2, false);
+    t[0x2B] = OpcodeInfo::new("ANC", C::opcode_anc, M::Immediate, 2, false);
+    t[0x4B] = OpcodeInfo::new("ALR", C::opcode_alr, M::Immediate, 2, false);
+    t[0x6B] = OpcodeInfo::new("ARR", C::opcode_arr, M::Immediate, 2, false);
+    t[0xCB] = OpcodeInfo::new("SBX", C::opcode_sbx, M::Immediate, 2, false);
+
+    // Unofficial unstable opcodes
+    t[0x8B] = OpcodeInfo::new("XAA", C::opcode_xaa, M::Immediate, 2, false);
+    t[0xAB] = OpcodeInfo::new("LXA", C::opcode_lxa, M::Immediate, 2, false);
+    t[0x9F] = OpcodeInfo::new("AHX", C::opcode_ahx, M::AbsoluteY, 5, false);
+    t[0x93] = OpcodeInfo::new("AHX", C::opcode_ahx, M::IndirectIndexed, 6, false);
+    t[0x9B] = OpcodeInfo::new("TAS", C::opcode_tas, M::AbsoluteY, 5, false);
+    t[0xBB] = OpcodeInfo::new("LAS", C::opcode_las, M::AbsoluteY, 4, true);
+    t[0x9E] = OpcodeInfo::new("SHX", C::opcode_shx, M::AbsoluteY, 5, false);
+    t[0x9C] = OpcodeInfo::new("SHY", C::opcode_shy, M::AbsoluteX, 5, false);
+
+    // Unofficial JAM, these take no cycles as the CPU halts
+    t[0x02] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x12] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x22] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x32] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x42] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x52] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x62] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x72] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0x92] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0xB2] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0xD2] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+    t[0xF2] = OpcodeInfo::new("JAM", C::opcode_jam, M::Implied, 0, false);
+
+    t
+};
+
+impl Default for MOS6502 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 // Main CPU class
 impl MOS6502 {
@@ -51,12 +503,51 @@ impl MOS6502 {
             y: 0x00,
             s: 0x00,
             pc: 0x0000,
-            sp: -1,
+            sp: 0xFD,
             clk: 0,
-            acc_addr: false,
+            jammed: false,
+            decimal: false,
+            nmi_pending: false,
+            nmi_line: false,
+            irq_pending: false,
+            take_nmi: false,
+            take_irq: false,
+            unstable: UnstableMode::Common,
+            error: None,
         }
     }
 
+    // A stock NMOS 6502, with BCD arithmetic in ADC and SBC when the
+    // Decimal flag is set. The NES CPU has it disconnected, so new()
+    // leaves it out.
+    pub fn new_nmos() -> Self {
+        Self {
+            decimal: true,
+            ..Self::new()
+        }
+    }
+
+    // Set when the CPU has halted on a JAM opcode.
+    // Only a reset gets it going again.
+    pub fn is_jammed(&self) -> bool {
+        self.jammed
+    }
+
+    // Choose how the unstable unofficial opcodes are emulated
+    pub fn set_unstable_opcode_mode(&mut self, mode: UnstableMode) {
+        self.unstable = mode;
+    }
+
+    // Current value of the Program Counter
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    // Total clock cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+        self.clk
+    }
+
     // Set flag in the status register
     fn set_flag(&mut self, flag: Flags, val: bool) {
         if val {
@@ -68,39 +559,516 @@ impl MOS6502 {
 
     // Get the state of a flag from the status register
     fn get_flag(&self, flag: Flags) -> bool {
-        self.s & (flag as u8) > 0
+        self.s & (flag as u8) != 0
     }
 
-    // Push a byte onto the stack
-    fn stack_push(&mut self, byte: u8, bus: &mut Bus) -> Result<(), &str> {
-        if self.sp < 0xFF {
-            self.sp += 1;
-            bus.write(self.sp as u16 + 0x100, byte);
-            Ok(())
+    // Resolve the operand of an instruction from its addressing mode
+    fn resolve(&mut self, mode: AddressingMode, bus: &mut dyn CpuBus) -> Operand {
+        match mode {
+            AddressingMode::Implied => self.addr_implied(bus),
+            AddressingMode::Accumulator => self.addr_acc(bus),
+            AddressingMode::Immediate => self.addr_immediate(bus),
+            AddressingMode::Relative => self.addr_relative(bus),
+            AddressingMode::ZeroPage => self.addr_zero_pg(bus),
+            AddressingMode::ZeroPageX => self.addr_zero_pg_x(bus),
+            AddressingMode::ZeroPageY => self.addr_zero_pg_y(bus),
+            AddressingMode::Absolute => self.addr_absolute(bus),
+            AddressingMode::AbsoluteX => self.addr_absolute_x(bus),
+            AddressingMode::AbsoluteY => self.addr_absolute_y(bus),
+            AddressingMode::Indirect => self.addr_indirect(bus),
+            AddressingMode::IndexedIndirect => self.addr_idx_indirect(bus),
+            AddressingMode::IndirectIndexed => self.addr_indirect_idx(bus),
+        }
+    }
+
+    // Indexed absolute and (indirect),Y accesses first read from the
+    // address formed before the carry into the high byte is fixed up.
+    // Read instructions only do this when the index crossed a page,
+    // stores and read-modify-write instructions always do.
+    fn dummy_read(&mut self, bus: &mut dyn CpuBus, info: OpcodeInfo, operand: Operand) {
+        let indexed = matches!(
+            info.mode,
+            AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::IndirectIndexed
+        );
+
+        if let Operand::Address(addr, crossed) = operand {
+            if indexed && (crossed || !info.page_penalty) {
+                bus.read(if crossed { addr.wrapping_sub(0x0100) } else { addr });
+            }
+        }
+    }
+
+    // Read the value of an operand
+    fn read_operand(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        match operand {
+            Operand::Accumulator => self.a,
+            Operand::Immediate(byte) => byte,
+            Operand::Address(addr, _) => bus.read(addr),
+            Operand::Implied | Operand::Relative(_) => 0,
+        }
+    }
+
+    // Write a value to the accumulator or memory depending on the operand
+    fn write_operand(&mut self, bus: &mut dyn CpuBus, operand: Operand, byte: u8) {
+        match operand {
+            Operand::Accumulator => self.a = byte,
+            Operand::Address(addr, _) => bus.write(addr, byte),
+            _ => {}
+        }
+    }
+
+    // Set the Zero and Negative flags from a result
+    fn set_zn(&mut self, val: u8) {
+        self.set_flag(Flags::Zero, val == 0);
+        self.set_flag(Flags::Negative, val & 0x80 != 0);
+    }
+
+    // BCD arithmetic is enabled and the Decimal flag is set
+    fn decimal_active(&self) -> bool {
+        self.decimal && self.get_flag(Flags::Decimal)
+    }
+
+    // Add a byte and the carry bit to the accumulator.
+    // Shared by ADC and the unofficial opcodes built on it.
+    fn add(&mut self, byte: u8) {
+        if self.decimal_active() {
+            self.add_decimal(byte);
         } else {
-            Err("Stack Overflow Occurred.")
+            self.add_with_carry(byte);
         }
     }
 
-    // Pop a byte from the stack
-    fn stack_pop(&mut self, bus: &mut Bus) -> Result<u8, &str> {
-        if self.sp > -1 {
-            let byte = bus.read(self.sp as u16 + 0x100);
-            self.sp -= 1;
-            Ok(byte)
+    // Subtract a byte and the inverse of the carry bit from the accumulator.
+    // Shared by SBC and the unofficial opcodes built on it.
+    // In decimal mode the NMOS 6502 still sets every flag
+    // from the binary result, only A gets the BCD result.
+    fn subtract(&mut self, byte: u8) {
+        let a = self.a;
+        let borrow = !self.get_flag(Flags::Carry) as i16;
+
+        self.add_with_carry(!byte);
+
+        if self.decimal_active() {
+            let mut lo = (a & 0x0F) as i16 - (byte & 0x0F) as i16 - borrow;
+            let mut hi = (a >> 4) as i16 - (byte >> 4) as i16;
+
+            if lo < 0 {
+                lo -= 6;
+                hi -= 1;
+            }
+            if hi < 0 {
+                hi -= 6;
+            }
+
+            self.a = (((hi << 4) & 0xF0) | (lo & 0x0F)) as u8;
+        }
+    }
+
+    // Add a byte and the carry bit to the accumulator as two BCD digits.
+    // Follows the NMOS 6502: Zero comes from the binary sum, Negative and
+    // Overflow from the sum after adjusting only the low digit, and Carry
+    // from the fully adjusted sum.
+    fn add_decimal(&mut self, byte: u8) {
+        let carry = self.get_flag(Flags::Carry) as u16;
+        let binary = (self.a as u16 + byte as u16 + carry) & 0x00FF;
+
+        let mut lo = (self.a & 0x0F) as u16 + (byte & 0x0F) as u16 + carry;
+        let mut hi = (self.a >> 4) as u16 + (byte >> 4) as u16;
+
+        if lo > 0x09 {
+            lo += 0x06;
+        }
+        if lo > 0x0F {
+            hi += 1;
+        }
+
+        let half = ((hi << 4) & 0xF0) as u8;
+        self.set_flag(Flags::Zero, binary == 0);
+        self.set_flag(Flags::Negative, half & 0x80 != 0);
+        self.set_flag(Flags::Overflow, (self.a ^ half) & !(self.a ^ byte) & 0x80 != 0);
+
+        if hi > 0x09 {
+            hi += 0x06;
+        }
+
+        self.set_flag(Flags::Carry, hi > 0x0F);
+        self.a = (((hi << 4) & 0xF0) | (lo & 0x0F)) as u8;
+    }
+
+    // Add a byte and the carry bit to the accumulator.
+    // Sets the Carry, Zero, Overflow and Negative flags.
+    // Shared by ADC and SBC.
+    fn add_with_carry(&mut self, byte: u8) {
+        let sum = self.a as u16 + byte as u16 + self.get_flag(Flags::Carry) as u16;
+        let res = (sum & 0x00FF) as u8;
+
+        self.set_flag(Flags::Carry, sum > 0xFF);
+
+        // Overflow when both inputs have the same sign
+        // and the result has a different one
+        let overflow = (self.a ^ res) & (byte ^ res) & 0x80 != 0;
+        self.set_flag(Flags::Overflow, overflow);
+
+        self.a = res;
+        self.set_zn(self.a);
+    }
+
+    // Shift a byte left by one bit.
+    // The MSB is moved into the Carry bit. Sets the Zero and Negative flags.
+    fn shift_left(&mut self, byte: u8) -> u8 {
+        self.set_flag(Flags::Carry, byte & 0x80 != 0);
+        let res = byte << 1;
+        self.set_zn(res);
+        res
+    }
+
+    // Shift a byte right by one bit.
+    // The LSB is moved into the Carry bit. Bit 7 is always cleared,
+    // so the Negative flag is too.
+    fn shift_right(&mut self, byte: u8) -> u8 {
+        self.set_flag(Flags::Carry, byte & 0x01 != 0);
+        let res = byte >> 1;
+        self.set_zn(res);
+        res
+    }
+
+    // Rotate a byte left by one bit through the Carry bit.
+    // The MSB is moved into the Carry bit, the old Carry goes into the LSB.
+    fn rotate_left(&mut self, byte: u8) -> u8 {
+        let carry = self.get_flag(Flags::Carry) as u8;
+        self.set_flag(Flags::Carry, byte & 0x80 != 0);
+        let res = (byte << 1) | carry;
+        self.set_zn(res);
+        res
+    }
+
+    // Rotate a byte right by one bit through the Carry bit.
+    // The LSB is moved into the Carry bit, the old Carry goes into the MSB.
+    fn rotate_right(&mut self, byte: u8) -> u8 {
+        let carry = self.get_flag(Flags::Carry) as u8;
+        self.set_flag(Flags::Carry, byte & 0x01 != 0);
+        let res = (byte >> 1) | (carry << 7);
+        self.set_zn(res);
+        res
+    }
+
+    // Read-modify-write the accumulator or a byte of memory with the given
+    // operation. Like the hardware, the unmodified value is written back
+    // before the result. Returns the result.
+    fn modify(&mut self, bus: &mut dyn CpuBus, operand: Operand, op: fn(&mut Self, u8) -> u8) -> u8 {
+        let old = self.read_operand(bus, operand);
+        self.write_operand(bus, operand, old);
+
+        let res = op(self, old);
+        self.write_operand(bus, operand, res);
+
+        res
+    }
+
+    // Decide whether an unstable unofficial opcode should execute.
+    // In the Error mode step() stops before getting here.
+    fn unstable_allowed(&self) -> bool {
+        self.unstable == UnstableMode::Common
+    }
+
+    // The opcode is one of the unstable unofficial opcodes
+    fn is_unstable(opcode: u8) -> bool {
+        matches!(opcode, 0x8B | 0xAB | 0x9F | 0x93 | 0x9B | 0xBB | 0x9E | 0x9C)
+    }
+
+    // Store a value ANDed with the high byte of the base address plus one.
+    // Used by the unstable SHA/SHX/SHY/TAS stores. If indexing crossed a
+    // page, the high byte of the target address is replaced by the value.
+    fn store_and_high(&mut self, bus: &mut dyn CpuBus, addr: u16, index: u8, val: u8) {
+        let base = addr.wrapping_sub(index as u16);
+        let res = val & ((base >> 8) as u8).wrapping_add(1);
+
+        let addr = if base & 0xFF00 != addr & 0xFF00 {
+            ((res as u16) << 8) | (addr & 0x00FF)
+        } else {
+            addr
+        };
+
+        bus.write(addr, res);
+    }
+
+    // Add the sign extended relative offset to the PC if the branch is taken.
+    // Returns the additional clock cycles: +1 if the branch is taken and
+    // another +1 if it lands on a different page.
+    fn branch(&mut self, taken: bool, offset: u16) -> u8 {
+        if !taken {
+            return 0;
+        }
+
+        let old_pc = self.pc;
+        self.pc = self.pc.wrapping_add(offset);
+
+        if (self.pc & 0xFF00) != (old_pc & 0xFF00) {
+            2
         } else {
-            Err("Stack Underflow Occured.")
+            1
         }
     }
 
-    fn read_opcode(&mut self, bus: &mut Bus) -> u8 {
+    // Compare a register against a byte of memory.
+    // Sets the Carry, Zero and Negative flags from reg - byte without
+    // storing the result anywhere. Shared by CMP, CPX and CPY.
+    fn compare(&mut self, reg: u8, byte: u8) {
+        let res = reg.wrapping_sub(byte);
+
+        self.set_flag(Flags::Carry, reg >= byte);
+        self.set_flag(Flags::Zero, reg == byte);
+        self.set_flag(Flags::Negative, res & 0x80 != 0);
+    }
+
+    // Push a byte onto the stack
+    // The stack lives in page 1 ($0100-$01FF) and grows downwards.
+    // The Stack Pointer wraps around within the page.
+    fn stack_push(&mut self, byte: u8, bus: &mut dyn CpuBus) {
+        bus.write(0x0100 | self.sp as u16, byte);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    // Pop a byte from the stack
+    fn stack_pop(&mut self, bus: &mut dyn CpuBus) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        bus.read(0x0100 | self.sp as u16)
+    }
+
+    // Push a 16-bit word onto the stack, high byte first
+    fn stack_push_word(&mut self, word: u16, bus: &mut dyn CpuBus) {
+        self.stack_push((word >> 8) as u8, bus);
+        self.stack_push((word & 0x00FF) as u8, bus);
+    }
+
+    // Pop a 16-bit word from the stack, low byte first
+    fn stack_pop_word(&mut self, bus: &mut dyn CpuBus) -> u16 {
+        let lo = self.stack_pop(bus) as u16;
+        let hi = self.stack_pop(bus) as u16;
+        (hi << 8) | lo
+    }
+
+    fn read_opcode(&mut self, bus: &mut dyn CpuBus) -> u8 {
         let opcode = bus.read(self.pc);
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         opcode
     }
 
-    fn tick(&mut self) {
+    // Read a 16-bit little endian word from memory
+    fn read_word(&mut self, bus: &mut dyn CpuBus, addr: u16) -> u16 {
+        let lo = bus.read(addr) as u16;
+        let hi = bus.read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    // Power the CPU on.
+    // The registers are cleared and the CPU goes through a reset,
+    // which leaves the status at $24 and SP at $FD.
+    pub fn power_on(&mut self, bus: &mut dyn CpuBus) {
+        self.a = 0x00;
+        self.x = 0x00;
+        self.y = 0x00;
+        self.sp = 0x00;
+        self.s = Flags::Unused as u8;
+
+        self.reset(bus);
+    }
+
+    // Reset the CPU, like pressing the reset button.
+    // The reset sequence runs like an interrupt with the stack writes
+    // suppressed, so SP goes down by 3 and nothing is pushed. The
+    // Interrupt Disable flag is set and the other registers are kept.
+    // Execution starts at the address held in the reset vector ($FFFC/$FFFD).
+    pub fn reset(&mut self, bus: &mut dyn CpuBus) {
+        self.sp = self.sp.wrapping_sub(3);
+        self.set_flag(Flags::Interrupt, true);
+        self.jammed = false;
+        self.error = None;
+        self.nmi_pending = false;
+        self.take_nmi = false;
+        self.take_irq = false;
+
+        self.pc = self.read_word(bus, 0xFFFC);
+
+        // Reset takes 7 clock cycles
+        self.clk += 7;
+    }
+
+    // Push the status register on to the stack.
+    // The Break bit only exists in the pushed copy of the status and
+    // is set when the push comes from an instruction (BRK or PHP).
+    // The Unused bit is always pushed as set.
+    fn push_status(&mut self, bus: &mut dyn CpuBus, brk: bool) {
+        let mut status = (self.s & !(Flags::Break as u8)) | Flags::Unused as u8;
+        if brk {
+            status |= Flags::Break as u8;
+        }
+        self.stack_push(status, bus);
+    }
+
+    // Push the PC and status register on to the stack.
+    fn push_state(&mut self, bus: &mut dyn CpuBus, brk: bool) {
+        self.stack_push_word(self.pc, bus);
+        self.push_status(bus, brk);
+    }
+
+    // Pull the status register from the stack.
+    // The pulled Break bit is ignored and the Unused bit is always set.
+    fn pull_status(&mut self, bus: &mut dyn CpuBus) {
+        self.s = self.stack_pop(bus);
+
+        self.set_flag(Flags::Break, false);
+        self.set_flag(Flags::Unused, true);
+    }
+
+    // Push the CPU state and jump through the given vector.
+    // Shared by the hardware interrupts.
+    fn interrupt(&mut self, bus: &mut dyn CpuBus, vector: u16) {
+        self.push_state(bus, false);
+
+        self.set_flag(Flags::Interrupt, true);
+        self.pc = self.read_word(bus, vector);
+
+        // Interrupt entry takes 7 clock cycles
+        self.clk += 7;
+    }
+
+    // Maskable Interrupt Request
+    // Ignored when the Interrupt Disable flag is set,
+    // otherwise jumps through the IRQ vector ($FFFE/$FFFF).
+    pub fn irq(&mut self, bus: &mut dyn CpuBus) {
+        if !self.get_flag(Flags::Interrupt) {
+            self.interrupt(bus, 0xFFFE);
+        }
+    }
+
+    // Non-Maskable Interrupt
+    // Taken regardless of the Interrupt Disable flag,
+    // jumps through the NMI vector ($FFFA/$FFFB).
+    pub fn nmi(&mut self, bus: &mut dyn CpuBus) {
+        self.interrupt(bus, 0xFFFA);
+    }
+
+    // Request a Non-Maskable Interrupt.
+    // It is serviced once the next instruction boundary is polled.
+    pub fn set_nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    // Drive the IRQ line. The line is level triggered, so it stays
+    // asserted until the device that raised it releases it.
+    pub fn set_irq(&mut self, active: bool) {
+        self.irq_pending = active;
+    }
+
+    // Current levels of the NMI and IRQ lines
+    fn interrupt_lines(&self, bus: &dyn CpuBus) -> (bool, bool) {
+        (bus.nmi(), self.irq_pending || bus.irq())
+    }
+
+    // Poll the sampled interrupt lines. The hardware does this during
+    // the last cycle of an instruction, so an interrupt recognised here
+    // is serviced before the next opcode fetch.
+    // IRQs are masked by the given value of the Interrupt Disable flag.
+    // The NMI line is edge triggered, only a change from high to low
+    // (here: from inactive to active) requests an NMI, so a line that
+    // is held active does not fire again.
+    fn poll_interrupts(&mut self, (nmi, irq): (bool, bool), interrupt: bool) {
+        if nmi && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = nmi;
+
+        self.take_nmi = self.nmi_pending;
+        self.take_irq = irq && !interrupt;
+    }
+
+    // Fetch, decode and execute a single instruction, or service the
+    // interrupt recognised at the end of the previous one.
+    // Returns the number of clock cycles the instruction took.
+    // A jammed CPU takes no cycles, so 0 means the CPU has halted.
+    // 0 is also returned, with the PC left on the opcode, when an
+    // unstable opcode is met in the Error mode.
+    pub fn step(&mut self, bus: &mut dyn CpuBus) -> u8 {
+        if self.jammed {
+            return 0;
+        }
+
+        // NMI takes priority over IRQ
+        if self.take_nmi {
+            self.take_nmi = false;
+            self.nmi_pending = false;
+            self.interrupt(bus, 0xFFFA);
+            return 7;
+        }
+
+        if self.take_irq {
+            self.take_irq = false;
+            self.interrupt(bus, 0xFFFE);
+            return 7;
+        }
+
+        let opcode = self.read_opcode(bus);
+        let info = OPCODE_TABLE[opcode as usize];
+
+        if self.unstable == UnstableMode::Error && Self::is_unstable(opcode) {
+            let addr = self.pc.wrapping_sub(1);
+            self.pc = addr;
+            self.error = Some(EmuError::UnstableOpcode { opcode, addr });
+            return 0;
+        }
+
+        // Lines at the start of the operand fetch, see below
+        let early_lines = self.interrupt_lines(bus);
+
+        let operand = self.resolve(info.mode, bus);
+        self.dummy_read(bus, info, operand);
+
+        let old_interrupt = self.get_flag(Flags::Interrupt);
+
+        // Only instructions that read their operand take the extra cycle
+        // when indexing crosses a page, stores and RMW timings are fixed
+        let penalty = (info.page_penalty && operand.page_crossed()) as u8;
+
+        let extra = (info.op)(self, bus, operand);
+        let cycles = info.cycles + penalty + extra;
+        self.clk += cycles as u64;
+
+        // CLI, SEI and PLP change the Interrupt Disable flag after
+        // the interrupt poll, so the poll still sees the old value
+        let interrupt = if matches!(opcode, 0x58 | 0x78 | 0x28) {
+            old_interrupt
+        } else {
+            self.get_flag(Flags::Interrupt)
+        };
+
+        // A taken branch that stays on the same page polls during its
+        // operand fetch instead of on its last cycle, so an interrupt
+        // raised later in the branch waits for one more instruction
+        let lines = if info.mode == AddressingMode::Relative && extra == 1 {
+            early_lines
+        } else {
+            self.interrupt_lines(bus)
+        };
+        self.poll_interrupts(lines, interrupt);
+
+        cycles
+    }
+
+    // Like step(), but reports a halted CPU or a rejected unstable
+    // opcode as an error instead of quietly taking no cycles.
+    pub fn try_step(&mut self, bus: &mut dyn CpuBus) -> Result<u8, EmuError> {
+        let cycles = self.step(bus);
+
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        if self.jammed {
+            return Err(EmuError::Jammed(self.pc));
+        }
 
+        Ok(cycles)
     }
 
     /*
@@ -116,39 +1084,35 @@ impl MOS6502 {
 
     // Implied Addressing
     // CPU knows what to do, no args needed.
-    fn addr_implied(&mut self, bus: &mut Bus) {
-        unimplemented!();
+    fn addr_implied(&mut self, _bus: &mut dyn CpuBus) -> Operand {
+        Operand::Implied
     }
 
     // Accumulator Addressing
     // Used by operations that act directly on the accumulator.
-    fn addr_acc(&mut self, bus: &mut Bus) -> AddrRes {
-        self.acc_addr = true;
-        AddrRes::new(self.a as u16, false)
+    fn addr_acc(&mut self, _bus: &mut dyn CpuBus) -> Operand {
+        Operand::Accumulator
     }
 
     // Immediate Addressing
     // The byte right after the opcode is the argument.
-    fn addr_immediate(&mut self, bus: &mut Bus) -> AddrRes {
-        let byte = self.pc;
-        self.pc += 1;
-        AddrRes::new(byte, false)
+    fn addr_immediate(&mut self, bus: &mut dyn CpuBus) -> Operand {
+        let byte = bus.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        Operand::Immediate(byte)
     }
 
     // Relative Addressing
     // Used by Branch Instructions. The byte after the opcode
     // is the value by which the Program Counter needs to be offset.
-    // This is a signed byte so further calculation is required to
-    // convert the number from unsigned to signed (Using 2's complement)
-    fn addr_relative(&mut self, bus: &mut Bus) -> AddrRes {
-        let mut byte = bus.read(self.pc) as u16;
-        self.pc += 1;
-
-        if byte & 0x80 > 1 {
-            byte |= 0xFF00;
-        }
+    // This is a signed byte so it is sign extended to 16 bits
+    // (Using 2's complement), which lets the branch instructions
+    // move the PC backwards with a wrapping add.
+    fn addr_relative(&mut self, bus: &mut dyn CpuBus) -> Operand {
+        let byte = bus.read(self.pc) as i8 as u16;
+        self.pc = self.pc.wrapping_add(1);
 
-        AddrRes::new(byte, (self.pc + byte) & 0xFF00 > self.pc & 0xFF00)
+        Operand::Relative(byte)
     }
 
     // Zero Page Addressing
@@ -156,24 +1120,24 @@ impl MOS6502 {
     // The byte after the opcode points to the memory address
     // in the aforementioned range which has the actual arg
     // i.e byte_after_opcode -> argument.
-    fn addr_zero_pg(&mut self, bus: &mut Bus) -> AddrRes {
+    fn addr_zero_pg(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let addr = bus.read(self.pc) as u16;
-        self.pc += 1;
-        AddrRes::new(addr, false)
+        self.pc = self.pc.wrapping_add(1);
+        Operand::Address(addr, false)
     }
 
     // Absolute Addressing
     // The two bytes after the opcode form the 16-bit argument
     // NES == little endian so first byte is low byte
-    fn addr_absolute(&mut self, bus: &mut Bus) -> AddrRes {
+    fn addr_absolute(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let byte_lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let byte_hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
         let addr = (byte_hi << 8) | byte_lo;
 
-        AddrRes::new(addr, false)
+        Operand::Address(addr, false)
     }
 
     // Indirect Addressing
@@ -182,69 +1146,71 @@ impl MOS6502 {
     // The address + 1 -> high byte of the arg.
     // The high and low byte form a 16-bit argument.
     // This is used exclusively by the JMP opcode.
-    fn addr_indirect(&mut self, bus: &mut Bus) -> AddrRes {
-        let addr_hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+    fn addr_indirect(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let addr_lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
+        let addr_hi = bus.read(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
 
         let addr = (addr_hi << 8) | addr_lo;
 
+        // Hardware bug: if the pointer lies on a page boundary ($xxFF)
+        // the high byte is fetched from the start of the same page ($xx00)
         let addr_2 = if addr_lo == 0x00FF {
-            (((bus.read(addr & 0xFF00) as u16) << 8) | bus.read(addr) as u16)
+            ((bus.read(addr & 0xFF00) as u16) << 8) | bus.read(addr) as u16
         } else {
-            (((bus.read(addr + 1) as u16) << 8) | bus.read(addr) as u16)
+            ((bus.read(addr + 1) as u16) << 8) | bus.read(addr) as u16
         };
 
-        AddrRes::new(addr_2, false)
+        Operand::Address(addr_2, false)
     }
 
     // X-Indexed Zero Page Addressing
     // Basically ZPA but with X register contents added
-    fn addr_zero_pg_x(&mut self, bus: &mut Bus) -> AddrRes {
+    fn addr_zero_pg_x(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let addr = bus.read(self.pc).wrapping_add(self.x) as u16;
-        self.pc += 1;
-        AddrRes::new(addr, false)
+        self.pc = self.pc.wrapping_add(1);
+        Operand::Address(addr, false)
     }
 
     // Y-Indexed Zero Page Addressing
     // Basically ZPA but with Y register contents added
-    fn addr_zero_pg_y(&mut self, bus: &mut Bus) -> AddrRes {
+    fn addr_zero_pg_y(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let addr = bus.read(self.pc).wrapping_add(self.y) as u16;
-        self.pc += 1;
-        AddrRes::new(addr, false)
+        self.pc = self.pc.wrapping_add(1);
+        Operand::Address(addr, false)
     }
 
     // X-Indexed Absolute Address
     // Basically Absolute Addressing offset with the X reg value
-    fn addr_absolute_x(&mut self, bus: &mut Bus) -> AddrRes {
+    fn addr_absolute_x(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let byte_lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let byte_hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
         let mut addr = (byte_hi << 8) | byte_lo;
         addr = addr.wrapping_add(self.x as u16);
 
         let cycle = addr & 0xFF00 != byte_hi << 8;
 
-        AddrRes::new(addr, cycle)
+        Operand::Address(addr, cycle)
     }
 
     // Y-Indexed Absolute Address
     // Basically Absolute Addressing offset with the Y reg value
-    fn addr_absolute_y(&mut self, bus: &mut Bus) -> AddrRes {
+    fn addr_absolute_y(&mut self, bus: &mut dyn CpuBus) -> Operand {
         let byte_lo = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
         let byte_hi = bus.read(self.pc) as u16;
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
 
         let mut addr = (byte_hi << 8) | byte_lo;
         addr = addr.wrapping_add(self.y as u16);
 
         let cycle = addr & 0xFF00 != byte_hi << 8;
 
-        AddrRes::new(addr, cycle)
+        Operand::Address(addr, cycle)
     }
 
     // Indexed Indirect Addressing
@@ -252,16 +1218,17 @@ impl MOS6502 {
     // address_low_byte  = mem[b]
     // address_high_byte = mem[b + 1]
     // arg = mem[address]
-    fn addr_idx_indirect(&mut self, bus: &mut Bus) -> AddrRes {
-        let byte = bus.read(self.pc).wrapping_add(self.x) as u16;
-        self.pc += 1;
+    fn addr_idx_indirect(&mut self, bus: &mut dyn CpuBus) -> Operand {
+        let byte = bus.read(self.pc).wrapping_add(self.x);
+        self.pc = self.pc.wrapping_add(1);
 
-        let byte_lo = bus.read(byte) as u16;
-        let byte_hi = bus.read(byte + 1) as u16;
+        // The pointer wraps around within the zero page
+        let byte_lo = bus.read(byte as u16) as u16;
+        let byte_hi = bus.read(byte.wrapping_add(1) as u16) as u16;
 
         let addr = (byte_hi << 8) | byte_lo;
 
-        AddrRes::new(addr, false)
+        Operand::Address(addr, false)
     }
 
     // Indirect Indexed Addressing
@@ -269,18 +1236,19 @@ impl MOS6502 {
     // addr_low = mem[b]
     // addr_hi  = mem[b + 1]
     // arg = mem[addr + Y]
-    fn addr_indirect_idx(&mut self, bus: &mut Bus) -> AddrRes {
-        let byte = bus.read(self.pc) as u16;
-        self.pc += 1;
+    fn addr_indirect_idx(&mut self, bus: &mut dyn CpuBus) -> Operand {
+        let byte = bus.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+
+        // The pointer wraps around within the zero page
+        let byte_lo = bus.read(byte as u16) as u16;
+        let byte_hi = bus.read(byte.wrapping_add(1) as u16) as u16;
 
-        let byte_lo = bus.read(byte) as u16;
-        let byte_hi = bus.read(byte + 1) as u16;
+        let addr = ((byte_hi << 8) | byte_lo).wrapping_add(self.y as u16);
 
-        let addr = ((byte_hi << 8) | byte_lo) + self.y as u16;
-        
-        let cycle = addr & 0xFF00 != byte_lo << 8;
+        let cycle = addr & 0xFF00 != byte_hi << 8;
 
-        AddrRes::new(addr, false)
+        Operand::Address(addr, cycle)
     }
 
     /*
@@ -302,24 +1270,53 @@ impl MOS6502 {
         This instruction adds the contents of a memory location to the accumulator together with the carry bit. 
         If overflow occurs the carry bit is set, this enables multiple byte addition to be performed.
     */
-    fn opcode_adc(&mut self, bus: &mut Bus, addr_mode: fn(&mut Self, &Bus) -> AddrRes) -> u8 {
-        let addr_res = addr_mode(self, bus);
-        let byte = bus.read(addr_res.addr);
+    fn opcode_adc(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.add(byte);
 
-        let res = self.a as u16 + byte as u16 + self.get_flag(Flags::Carry) as u16;
+        0
+    }
 
-        self.set_flag(Flags::Carry, res > 255);
-        self.set_flag(Flags::Zero, res & 0x00FF == 0);
+    /* AHX - Store A AND X AND High Byte (Unofficial, Unstable)
+     * M = A&X&(H+1)
+     * Stores the AND of the accumulator, the X register and the high byte
+     * of the base address plus one.
+     */
+    fn opcode_ahx(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if self.unstable_allowed() {
+            self.store_and_high(bus, operand.addr(), self.y, self.a & self.x);
+        }
 
-        let overflow = (!(self.a as u16 ^ byte as u16) & (self.a as u16 ^ res)) & 0x80 > 1;
+        0
+    }
 
-        self.set_flag(Flags::Overflow, overflow);
-        self.set_flag(Flags::Negative, res & 0x80 > 1);
+    /* ALR - AND then Logical Shift Right (Unofficial)
+     * A,C,Z,N = (A&M)/2
+     * ANDs the accumulator with the immediate byte and then shifts the
+     * accumulator right, like AND #imm followed by LSR A.
+     */
+    fn opcode_alr(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
 
-        self.a = (res & 0x00FF) as u8;
+        self.a = self.shift_right(self.a & byte);
 
-        // Additional clock cycles do not depend on opcode execution
-        if addr_res.cycle { 1 } else { 0 }
+        0
+    }
+
+    /* ANC - AND with Carry (Unofficial)
+     * A,Z,N = A&M, C = N
+     * ANDs the accumulator with the immediate byte and then copies the
+     * Negative flag into the Carry flag.
+     */
+    fn opcode_anc(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.a &= byte;
+        self.set_zn(self.a);
+        self.set_flag(Flags::Carry, self.a & 0x80 != 0);
+
+        0
     }
 
     /*
@@ -328,20 +1325,35 @@ impl MOS6502 {
 
         A logical AND is performed, bit by bit, on the accumulator contents using the contents of a byte of memory.        
     */
-    fn opcode_and(&mut self, bus: &mut Bus, addr_mode: fn(&mut Self, &mut Bus) -> AddrRes) -> u8 {
-        let addr_res = addr_mode(self, bus);
-        let byte = bus.read(addr_res.addr);
+    fn opcode_and(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
 
         // Perform bitwise AND and reassign value
-        self.a = self.a & byte;
+        self.a &= byte;
+
+        // Set Zero and Negative flags
+        self.set_zn(self.a);
 
-        // Set Zero Flag
-        self.set_flag(Flags::Zero, self.a == 0);
-        // Set Negative Flag
-        self.set_flag(Flags::Negative, self.a & 0x80 > 0);
+        0
+    }
+
+    /* ARR - AND then Rotate Right (Unofficial)
+     * A,Z,N = (A&M)>>1|C<<7
+     * ANDs the accumulator with the immediate byte and then rotates the
+     * accumulator right. The flags differ from ROR: Carry is taken from
+     * bit 6 of the result and Overflow is bit 6 XOR bit 5 of the result.
+     */
+    fn opcode_arr(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
 
-        // Additional clock cycles do not depend on opcode execution
-        if addr_res.cycle { 1 } else { 0 }
+        let carry = self.get_flag(Flags::Carry) as u8;
+        self.a = ((self.a & byte) >> 1) | (carry << 7);
+
+        self.set_zn(self.a);
+        self.set_flag(Flags::Carry, self.a & 0x40 != 0);
+        self.set_flag(Flags::Overflow, ((self.a >> 6) ^ (self.a >> 5)) & 0x01 != 0);
+
+        0
     }
 
     /*
@@ -353,35 +1365,8 @@ impl MOS6502 {
         The effect of this operation is to multiply the memory contents by 2 (ignoring 2's complement considerations),
         setting the carry if the result will not fit in 8 bits.
     */
-    fn opcode_asl(&mut self, bus: &mut Bus, addr_mode: fn(&mut Self, &mut Bus) -> AddrRes) -> u8 {
-        let addr_res = addr_mode(self, bus);
-        // If accumulator addressing was performed then the CPU is operating on the
-        // Accumulator. Set var 'byte' accordingly
-        let mut byte = if self.acc_addr {
-            addr_res.addr as u8
-        } else {
-            bus.read(addr_res.addr)
-        };
-
-        // MSB is moved into Carry bit
-        self.set_flag(Flags::Carry, byte & 0x80 > 0);
-
-        byte = byte << 1;
-        
-        // Set Zero and Negative flags accordingly
-        self.set_flag(Flags::Zero, byte == 0);
-        self.set_flag(Flags::Negative, byte & 0x80 > 0);
-
-        // If Accumulator addressing was performed, reassign the new byte to the
-        // Accumulator else the memeory address
-        if self.acc_addr {
-            self.a = byte;
-        } else {
-            bus.write(addr_res.addr, byte);
-        }
-
-        // Unset the Accumulator addressing switch
-        self.acc_addr = false;
+    fn opcode_asl(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.modify(bus, operand, Self::shift_left);
 
         // Opcode execution never requires additional clock cycles
         0
@@ -391,54 +1376,16 @@ impl MOS6502 {
         BCC - Branch if Carry Clear
         If the carry flag is clear then add the relative displacement to the program counter to cause a branch to a new location.
     */
-    fn opcode_bcc(&mut self, bus: &mut Bus, addr_mode: fn(&mut Self, &mut Bus) -> AddrRes) -> u8 {
-        let addr_res = addr_mode(self, bus);
-        
-        // If the Carry flag is set, return with no additional clock cycles
-        if self.get_flag(Flags::Carry) {
-            return 0;
-        }
-
-        let old_pc = self.pc;
-
-        self.pc += addr_res.addr;
-
-        let mut additional_cycles = 1;
-
-        // The number of additional cycles is 1 if branch succeeds
-        // and an additional +1 if branch occurs to a new page
-        if (self.pc & 0xFF00) != (old_pc & 0xFF00) {
-            additional_cycles += 1;
-        }
-
-        additional_cycles
+    fn opcode_bcc(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(!self.get_flag(Flags::Carry), operand.addr())
     }
 
     /*
         BCS - Branch if Carry Set
         If the carry flag is set then add the relative displacement to the program counter to cause a branch to a new location.
     */
-    fn opcode_bcs(&mut self, bus: &mut Bus, addr_mode: fn(&mut Self, &mut Bus) -> AddrRes) -> u8 {
-        let addr_res = addr_mode(self, bus);
-        
-        // If the Carry flag is unset, return with no additional clock cycles
-        if !self.get_flag(Flags::Carry) {
-            return 0;
-        }
-
-        let old_pc = self.pc;
-
-        self.pc += addr_res.addr;
-
-        let mut additional_cycles = 1;
-
-        // The number of additional cycles is 1 if branch succeeds
-        // and an additional +1 if branch occurs to a new page
-        if (self.pc & 0xFF00) != (old_pc & 0xFF00) {
-            additional_cycles += 1;
-        }
-
-        additional_cycles
+    fn opcode_bcs(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(self.get_flag(Flags::Carry), operand.addr())
     }
 
     /*
@@ -446,24 +1393,8 @@ impl MOS6502 {
         If the zero flag is set then add the relative displacement to the 
         program counter to cause a branch to a new location.
     */
-    fn opcode_beq(&mut self, bus: &mut Bus, addr_mode: fn(&mut Self, &mut Bus) -> AddrRes) -> u8 {
-        let addr_res = addr_mode(self, bus);
-
-        if !self.get_flag(Flags::Zero) {
-            return 0;
-        }
-
-        let old_pc = self.pc;
-
-        self.pc += addr_res.addr;
-
-        let mut additional_cycles = if (self.pc & 0xFF00) != (old_pc & 0xFF00) {
-            2
-        } else {
-            1
-        };
-
-        additional_cycles
+    fn opcode_beq(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(self.get_flag(Flags::Zero), operand.addr())
     }
 
     /* BIT - Bit Test
@@ -473,16 +1404,15 @@ impl MOS6502 {
      * result is not saved. Bits 7 and 6 of the memory byte are copied
      * into the negative and overflow flags respectively.
      */
-    fn opcode_bit(&mut self, bus: &mut Bus, addr_mode: AddrMode) -> u8 {
-        let addr_res = addr_mode(self, bus);
-        let byte = bus.read(addr_res.addr);
+    fn opcode_bit(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
 
         // Set Zero flag to MEM & A
         self.set_flag(Flags::Zero, self.a & byte == 0);
         // Set Negative flag to the last bit of memory
-        self.set_flag(Flags::Negative, byte & 0x80 == 0);
+        self.set_flag(Flags::Negative, byte & 0x80 != 0);
         // Set overflow flag to bit 6 of memory
-        self.set_flag(Flags::Overflow, byte & 0x40 == 0);
+        self.set_flag(Flags::Overflow, byte & 0x40 != 0);
 
         0
     }
@@ -491,31 +1421,3112 @@ impl MOS6502 {
      * If the negative flag is set then add the relative displacement
      * to the program counter to cause a branch to a new location
      */
-    fn opcode_bmi(&mut self, bus: &mut Bus, addr_mode: AddrMode) -> u8 {
-        let byte = addr_mode(self, bus);
-
-        if self.get_flag(Flags::Negative) {
-            self.pc += byte.addr;
-
-            if byte.cycle {2} else {1}
-        } else {
-            0
-        }
+    fn opcode_bmi(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(self.get_flag(Flags::Negative), operand.addr())
     }
 
     /* BNE - Branch if Not Equal
      * If the zero flag is clear then add the relative displacement to
      * the program counter to cause a branch to a new location
      */
-    fn opcode_bne(&mut self, bus: &mut Bus, addr_mode: AddrMode) -> u8 {
-        let addr_res = addr_mode(self, bus);
+    fn opcode_bne(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(!self.get_flag(Flags::Zero), operand.addr())
+    }
 
-        if !self.get_flag(Flags::Zero) {
-            self.pc += addr_res.addr;
+    /* BPL - Branch if Positive
+     * If the negative flag is clear then add the relative displacement
+     * to the program counter to cause a branch to a new location
+     */
+    fn opcode_bpl(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(!self.get_flag(Flags::Negative), operand.addr())
+    }
 
-            if addr_res.cycle {2} else {1}
-        } else {
-            0
+    /* BRK - Force Interrupt
+     * Pushes the program counter and the status register on to the stack,
+     * sets the Interrupt Disable flag and loads the program counter from
+     * the IRQ vector ($FFFE/$FFFF).
+     */
+    fn opcode_brk(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        // BRK is followed by a padding byte, so the return address is
+        // two bytes past the BRK opcode
+        self.pc = self.pc.wrapping_add(1);
+
+        self.push_state(bus, true);
+
+        self.set_flag(Flags::Interrupt, true);
+        self.pc = self.read_word(bus, 0xFFFE);
+
+        0
+    }
+
+    /* BVC - Branch if Overflow Clear
+     * If the overflow flag is clear then add the relative displacement
+     * to the program counter to cause a branch to a new location
+     */
+    fn opcode_bvc(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(!self.get_flag(Flags::Overflow), operand.addr())
+    }
+
+    /* BVS - Branch if Overflow Set
+     * If the overflow flag is set then add the relative displacement
+     * to the program counter to cause a branch to a new location
+     */
+    fn opcode_bvs(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.branch(self.get_flag(Flags::Overflow), operand.addr())
+    }
+
+    /* CLC - Clear Carry Flag
+     * C = 0
+     */
+    fn opcode_clc(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Carry, false);
+
+        0
+    }
+
+    /* CLD - Clear Decimal Mode
+     * D = 0
+     * The NES ignores decimal mode but the flag can still be read back.
+     */
+    fn opcode_cld(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Decimal, false);
+
+        0
+    }
+
+    /* CLI - Clear Interrupt Disable
+     * I = 0
+     */
+    fn opcode_cli(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Interrupt, false);
+
+        0
+    }
+
+    /* CLV - Clear Overflow Flag
+     * V = 0
+     */
+    fn opcode_clv(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Overflow, false);
+
+        0
+    }
+
+    /* CMP - Compare
+     * Z,C,N = A-M
+     * Compares the contents of the accumulator with a byte of memory and
+     * sets the zero and carry flags as appropriate.
+     */
+    fn opcode_cmp(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.compare(self.a, byte);
+
+        0
+    }
+
+    /* CPX - Compare X Register
+     * Z,C,N = X-M
+     * Compares the contents of the X register with a byte of memory and
+     * sets the zero and carry flags as appropriate.
+     */
+    fn opcode_cpx(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.compare(self.x, byte);
+
+        0
+    }
+
+    /* CPY - Compare Y Register
+     * Z,C,N = Y-M
+     * Compares the contents of the Y register with a byte of memory and
+     * sets the zero and carry flags as appropriate.
+     */
+    fn opcode_cpy(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.compare(self.y, byte);
+
+        0
+    }
+
+    /* DCP - Decrement and Compare (Unofficial)
+     * M = M-1, Z,C,N = A-M
+     * Decrements a byte of memory and then compares the result with the
+     * accumulator, like DEC followed by CMP.
+     */
+    fn opcode_dcp(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let res = self.modify(bus, operand, |_, b| b.wrapping_sub(1));
+
+        self.compare(self.a, res);
+
+        0
+    }
+
+    /* DEC - Decrement Memory
+     * M,Z,N = M-1
+     * Subtracts one from the value held at a specified memory location
+     * setting the zero and negative flags as appropriate.
+     */
+    fn opcode_dec(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.modify(bus, operand, |_, b| b.wrapping_sub(1));
+
+        self.set_zn(byte);
+
+        0
+    }
+
+    /* DEX - Decrement X Register
+     * X,Z,N = X-1
+     * Subtracts one from the X register setting the zero and negative
+     * flags as appropriate.
+     */
+    fn opcode_dex(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.x = self.x.wrapping_sub(1);
+
+        self.set_zn(self.x);
+
+        0
+    }
+
+    /* DEY - Decrement Y Register
+     * Y,Z,N = Y-1
+     * Subtracts one from the Y register setting the zero and negative
+     * flags as appropriate.
+     */
+    fn opcode_dey(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.y = self.y.wrapping_sub(1);
+
+        self.set_zn(self.y);
+
+        0
+    }
+
+    /* EOR - Exclusive OR
+     * A,Z,N = A^M
+     * An exclusive OR is performed, bit by bit, on the accumulator contents
+     * using the contents of a byte of memory.
+     */
+    fn opcode_eor(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.a ^= byte;
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* IGN - Ignore (Unofficial)
+     * Unofficial NOP that fetches an operand and reads the addressed byte,
+     * discarding it. The read still happens since it can have side effects.
+     * Takes the page-cross penalty like any other read instruction.
+     */
+    fn opcode_ign(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.read_operand(bus, operand);
+
+        0
+    }
+
+    /* INC - Increment Memory
+     * M,Z,N = M+1
+     * Adds one to the value held at a specified memory location setting
+     * the zero and negative flags as appropriate.
+     */
+    fn opcode_inc(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.modify(bus, operand, |_, b| b.wrapping_add(1));
+
+        self.set_zn(byte);
+
+        0
+    }
+
+    /* INX - Increment X Register
+     * X,Z,N = X+1
+     * Adds one to the X register setting the zero and negative flags
+     * as appropriate.
+     */
+    fn opcode_inx(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.x = self.x.wrapping_add(1);
+
+        self.set_zn(self.x);
+
+        0
+    }
+
+    /* INY - Increment Y Register
+     * Y,Z,N = Y+1
+     * Adds one to the Y register setting the zero and negative flags
+     * as appropriate.
+     */
+    fn opcode_iny(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.y = self.y.wrapping_add(1);
+
+        self.set_zn(self.y);
+
+        0
+    }
+
+    /* ISC - Increment and Subtract (Unofficial)
+     * M = M+1, A,Z,C,N,V = A-M-(1-C)
+     * Increments a byte of memory and then subtracts the result from the
+     * accumulator, like INC followed by SBC.
+     */
+    fn opcode_isc(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let res = self.modify(bus, operand, |_, b| b.wrapping_add(1));
+
+        self.subtract(res);
+
+        0
+    }
+
+    /* JAM - Halt the CPU (Unofficial, also known as KIL)
+     * Locks up the CPU. The PC is left pointing at the JAM opcode and no
+     * further instructions are executed until the CPU is reset.
+     */
+    fn opcode_jam(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.pc = self.pc.wrapping_sub(1);
+        self.jammed = true;
+
+        0
+    }
+
+    /* JMP - Jump
+     * Sets the program counter to the address specified by the operand.
+     * The indirect form keeps the hardware bug where a pointer at $xxFF
+     * takes its high byte from $xx00 (see addr_indirect).
+     */
+    fn opcode_jmp(&mut self, _bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.pc = operand.addr();
+
+        0
+    }
+
+    /* JSR - Jump to Subroutine
+     * Pushes the address (minus one) of the return point on to the stack
+     * and then sets the program counter to the target memory address.
+     */
+    fn opcode_jsr(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        // PC points past the operand, the pushed address is that of
+        // the last byte of the JSR instruction.
+        let ret = self.pc.wrapping_sub(1);
+        self.stack_push_word(ret, bus);
+
+        self.pc = operand.addr();
+
+        0
+    }
+
+    /* LAS - Load A, X and Stack Pointer (Unofficial, Unstable)
+     * A,X,S,Z,N = M&S
+     * ANDs a byte of memory with the stack pointer and stores the result
+     * in the accumulator, the X register and the stack pointer.
+     */
+    fn opcode_las(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if !self.unstable_allowed() {
+            return 0;
+        }
+
+        let byte = self.read_operand(bus, operand) & self.sp;
+        self.a = byte;
+        self.x = byte;
+        self.sp = byte;
+
+        self.set_zn(byte);
+
+        0
+    }
+
+    /* LAX - Load Accumulator and X (Unofficial)
+     * A,X,Z,N = M
+     * Loads a byte of memory into both the accumulator and the X register
+     * setting the zero and negative flags as appropriate.
+     */
+    fn opcode_lax(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.a = self.read_operand(bus, operand);
+        self.x = self.a;
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* LDA - Load Accumulator
+     * A,Z,N = M
+     * Loads a byte of memory into the accumulator setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_lda(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.a = self.read_operand(bus, operand);
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* LDX - Load X Register
+     * X,Z,N = M
+     * Loads a byte of memory into the X register setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_ldx(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.x = self.read_operand(bus, operand);
+
+        self.set_zn(self.x);
+
+        0
+    }
+
+    /* LDY - Load Y Register
+     * Y,Z,N = M
+     * Loads a byte of memory into the Y register setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_ldy(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.y = self.read_operand(bus, operand);
+
+        self.set_zn(self.y);
+
+        0
+    }
+
+    /* LSR - Logical Shift Right
+     * A,C,Z,N = A/2 or M,C,Z,N = M/2
+     * Each of the bits in A or M is shifted one place to the right. The bit
+     * that was in bit 0 is shifted into the carry flag. Bit 7 is set to zero.
+     */
+    fn opcode_lsr(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.modify(bus, operand, Self::shift_right);
+
+        0
+    }
+
+    /* LXA - Load A and X AND Immediate (Unofficial, Unstable)
+     * A,X,Z,N = (A|$EE)&M
+     * Also known as ATX or OAL. Like XAA the result depends on the analog
+     * state of the chip, the commonly accepted magic constant $EE is ORed
+     * into the accumulator first.
+     */
+    fn opcode_lxa(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if self.unstable_allowed() {
+            let byte = self.read_operand(bus, operand);
+            self.a = (self.a | 0xEE) & byte;
+            self.x = self.a;
+            self.set_zn(self.a);
+        }
+
+        0
+    }
+
+    /* NOP - No Operation
+     * Causes no changes to the processor other than the normal incrementing
+     * of the program counter. Also used for the 1-byte unofficial NOPs.
+     */
+    fn opcode_nop(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        0
+    }
+
+    /* ORA - Logical Inclusive OR
+     * A,Z,N = A|M
+     * An inclusive OR is performed, bit by bit, on the accumulator contents
+     * using the contents of a byte of memory.
+     */
+    fn opcode_ora(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.a |= byte;
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* PHA - Push Accumulator
+     * Pushes a copy of the accumulator on to the stack.
+     */
+    fn opcode_pha(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.stack_push(self.a, bus);
+
+        0
+    }
+
+    /* PHP - Push Processor Status
+     * Pushes a copy of the status register on to the stack.
+     * The pushed copy always has the Break and Unused bits set.
+     */
+    fn opcode_php(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.push_status(bus, true);
+
+        0
+    }
+
+    /* PLA - Pull Accumulator
+     * Pulls a byte from the stack into the accumulator setting the zero
+     * and negative flags as appropriate.
+     */
+    fn opcode_pla(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.a = self.stack_pop(bus);
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* PLP - Pull Processor Status
+     * Pulls a byte from the stack into the status register.
+     */
+    fn opcode_plp(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.pull_status(bus);
+
+        0
+    }
+
+    /* RLA - Rotate Left and AND (Unofficial)
+     * M = M<<1|C, A,Z,N = A&M
+     * Rotates a byte of memory left through the carry and then ANDs the
+     * result into the accumulator, like ROL followed by AND.
+     */
+    fn opcode_rla(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let res = self.modify(bus, operand, Self::rotate_left);
+
+        self.a &= res;
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* ROL - Rotate Left
+     * Moves each of the bits in either A or M one place to the left. Bit 0
+     * is filled with the current value of the carry flag whilst the old
+     * bit 7 becomes the new carry flag value.
+     */
+    fn opcode_rol(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.modify(bus, operand, Self::rotate_left);
+
+        0
+    }
+
+    /* ROR - Rotate Right
+     * Moves each of the bits in either A or M one place to the right. Bit 7
+     * is filled with the current value of the carry flag whilst the old
+     * bit 0 becomes the new carry flag value.
+     */
+    fn opcode_ror(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.modify(bus, operand, Self::rotate_right);
+
+        0
+    }
+
+    /* RRA - Rotate Right and Add (Unofficial)
+     * M = C<<7|M>>1, A,Z,C,N,V = A+M+C
+     * Rotates a byte of memory right through the carry and then adds the
+     * result to the accumulator, like ROR followed by ADC.
+     */
+    fn opcode_rra(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let res = self.modify(bus, operand, Self::rotate_right);
+
+        self.add(res);
+
+        0
+    }
+
+    /* RTI - Return from Interrupt
+     * Pulls the status register and then the program counter from the
+     * stack. Unlike RTS the pulled address is not incremented.
+     */
+    fn opcode_rti(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.pull_status(bus);
+
+        self.pc = self.stack_pop_word(bus);
+
+        0
+    }
+
+    /* RTS - Return from Subroutine
+     * Pulls the program counter (minus one) from the stack. Used at the
+     * end of a subroutine to return to the calling routine.
+     */
+    fn opcode_rts(&mut self, bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        // JSR pushes the address of its last byte, so step past it
+        self.pc = self.stack_pop_word(bus).wrapping_add(1);
+
+        0
+    }
+
+    /* SAX - Store Accumulator AND X (Unofficial)
+     * M = A&X
+     * Stores the bitwise AND of the accumulator and the X register into
+     * memory. No flags are affected.
+     */
+    fn opcode_sax(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.write_operand(bus, operand, self.a & self.x);
+
+        0
+    }
+
+    /* SBC - Subtract with Carry
+     * A,Z,C,N = A-M-(1-C)
+     * Subtracts the contents of a memory location and the inverse of the
+     * carry bit from the accumulator. The carry bit is cleared if a borrow
+     * occurs. This is the same as adding the one's complement of the
+     * operand, so the flag logic is shared with ADC.
+     */
+    fn opcode_sbc(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        self.subtract(byte);
+
+        0
+    }
+
+    /* SBX - Subtract from A AND X (Unofficial, also known as AXS)
+     * X = (A&X)-M, Z,C,N as CMP
+     * Subtracts the immediate byte from the AND of the accumulator and
+     * the X register without borrow and stores the result in X. The flags
+     * are set like a compare of A&X against the immediate byte.
+     */
+    fn opcode_sbx(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let byte = self.read_operand(bus, operand);
+
+        let reg = self.a & self.x;
+        self.compare(reg, byte);
+        self.x = reg.wrapping_sub(byte);
+
+        0
+    }
+
+    /* SEC - Set Carry Flag
+     * C = 1
+     */
+    fn opcode_sec(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Carry, true);
+
+        0
+    }
+
+    /* SED - Set Decimal Flag
+     * D = 1
+     * The NES ignores decimal mode but the flag can still be read back.
+     */
+    fn opcode_sed(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Decimal, true);
+
+        0
+    }
+
+    /* SEI - Set Interrupt Disable
+     * I = 1
+     */
+    fn opcode_sei(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.set_flag(Flags::Interrupt, true);
+
+        0
+    }
+
+    /* SHX - Store X AND High Byte (Unofficial, Unstable)
+     * M = X&(H+1)
+     * Stores the AND of the X register and the high byte of the base
+     * address plus one.
+     */
+    fn opcode_shx(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if self.unstable_allowed() {
+            self.store_and_high(bus, operand.addr(), self.y, self.x);
+        }
+
+        0
+    }
+
+    /* SHY - Store Y AND High Byte (Unofficial, Unstable)
+     * M = Y&(H+1)
+     * Stores the AND of the Y register and the high byte of the base
+     * address plus one.
+     */
+    fn opcode_shy(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if self.unstable_allowed() {
+            self.store_and_high(bus, operand.addr(), self.x, self.y);
+        }
+
+        0
+    }
+
+    /* SLO - Shift Left and OR (Unofficial)
+     * M = M<<1, A,Z,N = A|M
+     * Shifts a byte of memory left and then ORs the result into the
+     * accumulator, like ASL followed by ORA.
+     */
+    fn opcode_slo(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let res = self.modify(bus, operand, Self::shift_left);
+
+        self.a |= res;
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* SRE - Shift Right and EOR (Unofficial)
+     * M = M>>1, A,Z,N = A^M
+     * Shifts a byte of memory right and then EORs the result into the
+     * accumulator, like LSR followed by EOR.
+     */
+    fn opcode_sre(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        let res = self.modify(bus, operand, Self::shift_right);
+
+        self.a ^= res;
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* STA - Store Accumulator
+     * M = A
+     * Stores the contents of the accumulator into memory.
+     */
+    fn opcode_sta(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.write_operand(bus, operand, self.a);
+
+        0
+    }
+
+    /* STX - Store X Register
+     * M = X
+     * Stores the contents of the X register into memory.
+     */
+    fn opcode_stx(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.write_operand(bus, operand, self.x);
+
+        0
+    }
+
+    /* STY - Store Y Register
+     * M = Y
+     * Stores the contents of the Y register into memory.
+     */
+    fn opcode_sty(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        self.write_operand(bus, operand, self.y);
+
+        0
+    }
+
+    /* TAS - Transfer A AND X to Stack Pointer (Unofficial, Unstable)
+     * S = A&X, M = S&(H+1)
+     * Stores the AND of the accumulator and the X register in the stack
+     * pointer, then stores the stack pointer ANDed with the high byte of
+     * the base address plus one.
+     */
+    fn opcode_tas(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if self.unstable_allowed() {
+            self.sp = self.a & self.x;
+            self.store_and_high(bus, operand.addr(), self.y, self.sp);
+        }
+
+        0
+    }
+
+    /* TAX - Transfer Accumulator to X
+     * X = A
+     * Copies the accumulator into the X register setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_tax(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.x = self.a;
+
+        self.set_zn(self.x);
+
+        0
+    }
+
+    /* TAY - Transfer Accumulator to Y
+     * Y = A
+     * Copies the accumulator into the Y register setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_tay(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.y = self.a;
+
+        self.set_zn(self.y);
+
+        0
+    }
+
+    /* TSX - Transfer Stack Pointer to X
+     * X = S
+     * Copies the stack pointer into the X register setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_tsx(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.x = self.sp;
+
+        self.set_zn(self.x);
+
+        0
+    }
+
+    /* TXA - Transfer X to Accumulator
+     * A = X
+     * Copies the X register into the accumulator setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_txa(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.a = self.x;
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* TXS - Transfer X to Stack Pointer
+     * S = X
+     * Copies the X register into the stack pointer. Unlike the other
+     * transfers, no flags are affected.
+     */
+    fn opcode_txs(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.sp = self.x;
+
+        0
+    }
+
+    /* TYA - Transfer Y to Accumulator
+     * A = Y
+     * Copies the Y register into the accumulator setting the zero and
+     * negative flags as appropriate.
+     */
+    fn opcode_tya(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        self.a = self.y;
+
+        self.set_zn(self.a);
+
+        0
+    }
+
+    /* XAA - Transfer X AND Immediate to A (Unofficial, Unstable)
+     * A,Z,N = (A|$EE)&X&M
+     * The result depends on the analog state of the chip. The commonly
+     * accepted magic constant $EE is ORed into the accumulator first.
+     */
+    fn opcode_xaa(&mut self, bus: &mut dyn CpuBus, operand: Operand) -> u8 {
+        if self.unstable_allowed() {
+            let byte = self.read_operand(bus, operand);
+            self.a = (self.a | 0xEE) & self.x & byte;
+            self.set_zn(self.a);
+        }
+
+        0
+    }
+
+    // Placeholder for opcodes that aren't implemented.
+    // Behaves like a NOP.
+    fn opcode_xxx(&mut self, _bus: &mut dyn CpuBus, _operand: Operand) -> u8 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::{Cartridge, Mirroring};
+    use crate::cpu_bus::Bus;
+    use crate::mapper::{FlatRam, Mapper};
+
+    // Load a program at $0600 and point the PC at it
+    fn setup(program: &[u8]) -> (MOS6502, Bus) {
+        let mut bus = Bus::new();
+        for (i, byte) in program.iter().enumerate() {
+            bus.write(0x0600 + i as u16, *byte);
+        }
+
+        let mut cpu = MOS6502::new();
+        cpu.pc = 0x0600;
+
+        (cpu, bus)
+    }
+
+    #[test]
+    fn step_runs_a_program_to_its_end() {
+        // ADC #$10 / BCC +2 / AND #$00 / ASL A / BEQ +1 / NOP
+        let (mut cpu, mut bus) = setup(&[0x69, 0x10, 0x90, 0x02, 0x29, 0x00, 0x0A, 0xF0, 0x01, 0xEA]);
+
+        let mut steps = 0;
+        let mut cycles = 0;
+        while cpu.pc != 0x060A {
+            cycles += cpu.step(&mut bus) as u32;
+            steps += 1;
+            assert!(steps < 10, "program never reached its end");
+        }
+
+        // The AND is branched over
+        assert_eq!(steps, 5);
+        assert_eq!(cycles, 2 + 3 + 2 + 2 + 2);
+        assert_eq!(cpu.a, 0x20);
+    }
+
+    #[test]
+    fn step_advances_pc_and_cycles() {
+        // ADC #$01 / ADC $0200 / NOP
+        let (mut cpu, mut bus) = setup(&[0x69, 0x01, 0x6D, 0x00, 0x02, 0xEA]);
+        bus.write(0x0200, 0x02);
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc(), 0x0602);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.pc(), 0x0605);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc(), 0x0606);
+
+        assert_eq!(cpu.cycles(), 8);
+        assert_eq!(cpu.a, 0x03);
+    }
+
+    #[test]
+    fn branch_backward_across_page() {
+        let (mut cpu, mut bus) = setup(&[]);
+
+        // BNE -6 at $0700 lands on $06FC
+        bus.write(0x0700, 0xD0);
+        bus.write(0x0701, 0xFA);
+        cpu.pc = 0x0700;
+
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.pc, 0x06FC);
+    }
+
+    #[test]
+    fn lda_sets_zero_and_negative() {
+        let (mut cpu, mut bus) = setup(&[0xA9, 0x00, 0xA9, 0x80]);
+
+        cpu.step(&mut bus);
+        assert!(cpu.get_flag(Flags::Zero));
+        assert!(!cpu.get_flag(Flags::Negative));
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x80);
+        assert!(!cpu.get_flag(Flags::Zero));
+        assert!(cpu.get_flag(Flags::Negative));
+    }
+
+    #[test]
+    fn lda_absolute_x_page_cross() {
+        // LDA $04FF,X / LDA $0400,X
+        let (mut cpu, mut bus) = setup(&[0xBD, 0xFF, 0x04, 0xBD, 0x00, 0x04]);
+        bus.write(0x0500, 0x42);
+        bus.write(0x0401, 0x24);
+        cpu.x = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.a, 0x42);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.a, 0x24);
+    }
+
+    #[test]
+    fn jmp_indirect_page_wrap_bug() {
+        // JMP ($10FF)
+        let (mut cpu, mut bus) = setup(&[0x6C, 0xFF, 0x10]);
+        bus.write(0x10FF, 0x34);
+        bus.write(0x1000, 0x12);
+        bus.write(0x1100, 0x56);
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    #[test]
+    fn ldx_ldy_flags_and_page_cross() {
+        // LDY $04FF,X / LDX $0481,Y / LDX #$00
+        let (mut cpu, mut bus) = setup(&[0xBC, 0xFF, 0x04, 0xBE, 0x81, 0x04, 0xA2, 0x00]);
+        bus.write(0x0500, 0x80);
+        bus.write(0x0501, 0x7F);
+        cpu.x = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.y, 0x80);
+        assert!(cpu.get_flag(Flags::Negative));
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.x, 0x7F);
+        assert!(!cpu.get_flag(Flags::Negative));
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert!(cpu.get_flag(Flags::Zero));
+    }
+
+    #[test]
+    fn stores_every_mode() {
+        // (instruction, target address, value stored, cycles)
+        let cases: [(&[u8], u16, u8, u8); 13] = [
+            (&[0x85, 0x10], 0x0010, 0x11, 3),
+            (&[0x95, 0x10], 0x0012, 0x11, 4),
+            (&[0x8D, 0x00, 0x04], 0x0400, 0x11, 4),
+            (&[0x9D, 0x00, 0x04], 0x0402, 0x11, 5),
+            (&[0x99, 0x00, 0x04], 0x0403, 0x11, 5),
+            (&[0x81, 0x1E], 0x0400, 0x11, 6),
+            (&[0x91, 0x20], 0x0403, 0x11, 6),
+            (&[0x86, 0x10], 0x0010, 0x02, 3),
+            (&[0x96, 0x10], 0x0013, 0x02, 4),
+            (&[0x8E, 0x00, 0x04], 0x0400, 0x02, 4),
+            (&[0x84, 0x10], 0x0010, 0x03, 3),
+            (&[0x94, 0x10], 0x0012, 0x03, 4),
+            (&[0x8C, 0x00, 0x04], 0x0400, 0x03, 4),
+        ];
+
+        for (program, addr, val, cycles) in cases.iter() {
+            let (mut cpu, mut bus) = setup(program);
+            bus.write(0x0020, 0x00);
+            bus.write(0x0021, 0x04);
+            cpu.a = 0x11;
+            cpu.x = 0x02;
+            cpu.y = 0x03;
+
+            assert_eq!(cpu.step(&mut bus), *cycles, "{:02X}", program[0]);
+            assert_eq!(bus.read(*addr), *val, "{:02X}", program[0]);
+        }
+    }
+
+    #[test]
+    fn sta_absolute_x_page_cross_takes_no_extra_cycle() {
+        // STA $04FF,X
+        let (mut cpu, mut bus) = setup(&[0x9D, 0xFF, 0x04]);
+        cpu.a = 0x5A;
+        cpu.x = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0500), 0x5A);
+    }
+
+    #[test]
+    fn transfers_set_flags_and_take_one_byte() {
+        // TAX / TAY / TXA / TYA
+        for &val in &[0x00u8, 0xFF] {
+            for &opcode in &[0xAAu8, 0xA8, 0x8A, 0x98] {
+                let (mut cpu, mut bus) = setup(&[opcode]);
+                cpu.a = val;
+                cpu.x = val;
+                cpu.y = val;
+
+                assert_eq!(cpu.step(&mut bus), 2);
+                assert_eq!(cpu.pc, 0x0601);
+                assert_eq!(cpu.get_flag(Flags::Zero), val == 0x00, "{:02X}", opcode);
+                assert_eq!(cpu.get_flag(Flags::Negative), val == 0xFF, "{:02X}", opcode);
+            }
+        }
+
+        // TAX / TYA
+        let (mut cpu, mut bus) = setup(&[0xAA, 0x98]);
+        cpu.a = 0x42;
+        cpu.y = 0x80;
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.x, 0x42);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x80);
+    }
+
+    #[test]
+    fn stack_push_wraps_within_page_one() {
+        let (mut cpu, mut bus) = setup(&[]);
+        cpu.sp = 0x00;
+
+        cpu.stack_push(0x11, &mut bus);
+        cpu.stack_push(0x22, &mut bus);
+
+        assert_eq!(cpu.sp, 0xFE);
+        assert_eq!(bus.read(0x0100), 0x11);
+        assert_eq!(bus.read(0x01FF), 0x22);
+
+        assert_eq!(cpu.stack_pop(&mut bus), 0x22);
+        assert_eq!(cpu.stack_pop(&mut bus), 0x11);
+        assert_eq!(cpu.sp, 0x00);
+    }
+
+    #[test]
+    fn txs_moves_the_stack() {
+        // LDX #$FD / TXS
+        let (mut cpu, mut bus) = setup(&[0xA2, 0xFD, 0x9A]);
+        cpu.sp = 0x42;
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.sp, 0xFD);
+
+        cpu.stack_push(0x77, &mut bus);
+        assert_eq!(bus.read(0x01FD), 0x77);
+        assert_eq!(cpu.sp, 0xFC);
+    }
+
+    #[test]
+    fn tsx_of_zero_sets_zero() {
+        // TSX
+        let (mut cpu, mut bus) = setup(&[0xBA]);
+        cpu.sp = 0x00;
+        cpu.x = 0x42;
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.x, 0x00);
+        assert!(cpu.get_flag(Flags::Zero));
+    }
+
+    #[test]
+    fn pha_pla_round_trip() {
+        // PHA / LDA #$00 / PLA
+        let (mut cpu, mut bus) = setup(&[0x48, 0xA9, 0x00, 0x68]);
+        cpu.a = 0x80;
+
+        assert_eq!(cpu.step(&mut bus), 3);
+        cpu.step(&mut bus);
+        assert!(cpu.get_flag(Flags::Zero));
+
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.a, 0x80);
+        assert!(cpu.get_flag(Flags::Negative));
+        assert!(!cpu.get_flag(Flags::Zero));
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn php_plp_masks_break() {
+        // PHP / PLP
+        let (mut cpu, mut bus) = setup(&[0x08, 0x28]);
+        cpu.s = 0xC3;
+
+        cpu.step(&mut bus);
+        // Break and Unused are set in the pushed copy only
+        assert_eq!(bus.read(0x01FD), 0xF3);
+        assert_eq!(cpu.s, 0xC3);
+
+        cpu.step(&mut bus);
+        // The pulled Break bit is dropped, Unused is always set
+        assert_eq!(cpu.s, 0xE3);
+    }
+
+    #[test]
+    fn reset_loads_the_vector() {
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0xFFFC, 0x00);
+        bus.write(0xFFFD, 0xC0);
+
+        // LDA #$42 at the vector
+        bus.write(0xC000, 0xA9);
+        bus.write(0xC001, 0x42);
+
+        cpu.reset(&mut bus);
+        assert_eq!(cpu.pc, 0xC000);
+        assert!(cpu.get_flag(Flags::Interrupt));
+        assert_eq!(cpu.cycles(), 7);
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.a, 0x42);
+        assert_eq!(cpu.pc, 0xC002);
+        assert_eq!(cpu.cycles(), 9);
+    }
+
+    #[test]
+    fn jsr_pushes_return_address_minus_one() {
+        // JSR $8000
+        let (mut cpu, mut bus) = setup(&[0x20, 0x00, 0x80]);
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.pc, 0x8000);
+        assert_eq!(cpu.sp, 0xFB);
+        assert_eq!(bus.read(0x01FD), 0x06);
+        assert_eq!(bus.read(0x01FC), 0x02);
+    }
+
+    #[test]
+    fn jmp_absolute() {
+        // JMP $1234
+        let (mut cpu, mut bus) = setup(&[0x4C, 0x34, 0x12]);
+
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    #[test]
+    fn irq_respects_interrupt_disable() {
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0xFFFE, 0x00);
+        bus.write(0xFFFF, 0x90);
+
+        cpu.s = 0x04;
+        cpu.irq(&mut bus);
+        assert_eq!(cpu.pc, 0x0600);
+        assert_eq!(cpu.sp, 0xFD);
+
+        cpu.s = 0x00;
+        cpu.irq(&mut bus);
+        assert_eq!(cpu.pc, 0x9000);
+        assert!(cpu.get_flag(Flags::Interrupt));
+        assert_eq!(bus.read(0x01FD), 0x06);
+        assert_eq!(bus.read(0x01FC), 0x00);
+        // Hardware interrupts push the status with Break clear
+        assert_eq!(bus.read(0x01FB), 0x20);
+    }
+
+    #[test]
+    fn nested_subroutines_return() {
+        // JSR $0700 / NOP, $0700: JSR $0710 / RTS, $0710: RTS
+        let (mut cpu, mut bus) = setup(&[0x20, 0x00, 0x07, 0xEA]);
+        bus.write(0x0700, 0x20);
+        bus.write(0x0701, 0x10);
+        bus.write(0x0702, 0x07);
+        bus.write(0x0703, 0x60);
+        bus.write(0x0710, 0x60);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0710);
+        assert_eq!(cpu.sp, 0xF9);
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.pc, 0x0703);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0603);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn rts_across_page() {
+        let (mut cpu, mut bus) = setup(&[]);
+
+        // JSR $0800 at $06FD, its last byte is at $06FF
+        bus.write(0x06FD, 0x20);
+        bus.write(0x06FE, 0x00);
+        bus.write(0x06FF, 0x08);
+        bus.write(0x0800, 0x60);
+        cpu.pc = 0x06FD;
+
+        cpu.step(&mut bus);
+        assert_eq!(bus.read(0x01FD), 0x06);
+        assert_eq!(bus.read(0x01FC), 0xFF);
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0700);
+    }
+
+    #[test]
+    fn brk_and_rti() {
+        // BRK / padding / NOP, handler at $8000: RTI
+        let (mut cpu, mut bus) = setup(&[0x00, 0xFF, 0xEA]);
+        bus.write(0xFFFE, 0x00);
+        bus.write(0xFFFF, 0x80);
+        bus.write(0x8000, 0x40);
+        cpu.s = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x8000);
+        assert_eq!(bus.read(0x01FD), 0x06);
+        assert_eq!(bus.read(0x01FC), 0x02);
+        assert_eq!(bus.read(0x01FB), 0x31);
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.pc, 0x0602);
+        assert_eq!(cpu.s, 0x21);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn brk_sets_break_in_pushed_status_only() {
+        // BRK
+        let (mut cpu, mut bus) = setup(&[0x00]);
+        bus.write(0xFFFE, 0x34);
+        bus.write(0xFFFF, 0x12);
+        cpu.s = 0x20;
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x1234);
+        assert_ne!(bus.read(0x01FB) & 0x10, 0);
+        assert!(!cpu.get_flag(Flags::Break));
+        assert!(cpu.get_flag(Flags::Interrupt));
+    }
+
+    #[test]
+    fn rti_restores_status_and_pc() {
+        // RTI
+        let (mut cpu, mut bus) = setup(&[0x40]);
+        cpu.stack_push(0x12, &mut bus);
+        cpu.stack_push(0x34, &mut bus);
+        cpu.stack_push(0xC3, &mut bus);
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.s, 0xE3);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn sbc_overflow_and_borrow() {
+        // SBC #$B0 with Carry set
+        let (mut cpu, mut bus) = setup(&[0xE9, 0xB0]);
+        cpu.a = 0x50;
+        cpu.set_flag(Flags::Carry, true);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0xA0);
+        assert!(cpu.get_flag(Flags::Overflow));
+        assert!(!cpu.get_flag(Flags::Carry));
+        assert!(cpu.get_flag(Flags::Negative));
+
+        // SBC #$01 with Carry clear
+        let (mut cpu, mut bus) = setup(&[0xE9, 0x01]);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0xFE);
+        assert!(!cpu.get_flag(Flags::Overflow));
+        assert!(!cpu.get_flag(Flags::Carry));
+    }
+
+    #[test]
+    fn sbc_16_bit_borrow() {
+        // $0100 - $0001 = $00FF
+        // LDA #$00 / SBC #$01 / STA $10 / LDA #$01 / SBC #$00 / STA $11
+        let (mut cpu, mut bus) = setup(&[
+            0xA9, 0x00, 0xE9, 0x01, 0x85, 0x10, 0xA9, 0x01, 0xE9, 0x00, 0x85, 0x11,
+        ]);
+        cpu.set_flag(Flags::Carry, true);
+
+        for _ in 0..6 {
+            cpu.step(&mut bus);
+        }
+
+        assert_eq!(bus.read(0x0010), 0xFF);
+        assert_eq!(bus.read(0x0011), 0x00);
+        assert!(cpu.get_flag(Flags::Carry));
+        assert!(cpu.get_flag(Flags::Zero));
+    }
+
+    #[test]
+    fn jsr_rts_returns_after_the_call() {
+        // JSR $0700 / NOP, $0700: RTS
+        let (mut cpu, mut bus) = setup(&[0x20, 0x00, 0x07, 0xEA]);
+        bus.write(0x0700, 0x60);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0603);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn compares_are_unsigned() {
+        // (register, operand, C, Z, N)
+        let cases = [
+            (0x10u8, 0x20u8, false, false, true),
+            (0x20, 0x20, true, true, false),
+            (0x20, 0x10, true, false, false),
+            (0x80, 0x7F, true, false, false),
+            (0x7F, 0x80, false, false, true),
+            (0xFF, 0x80, true, false, false),
+            (0x00, 0xFF, false, false, false),
+        ];
+
+        // CMP #, CPX #, CPY #
+        for &opcode in &[0xC9u8, 0xE0, 0xC0] {
+            for &(reg, byte, carry, zero, negative) in cases.iter() {
+                let (mut cpu, mut bus) = setup(&[opcode, byte]);
+                cpu.a = reg;
+                cpu.x = reg;
+                cpu.y = reg;
+
+                cpu.step(&mut bus);
+                let flags = (
+                    cpu.get_flag(Flags::Carry),
+                    cpu.get_flag(Flags::Zero),
+                    cpu.get_flag(Flags::Negative),
+                );
+                assert_eq!(flags, (carry, zero, negative), "{:02X} {:02X} {:02X}", opcode, reg, byte);
+            }
+        }
+    }
+
+    #[test]
+    fn inc_dec_memory_wrap() {
+        // INC $10 / DEC $11
+        let (mut cpu, mut bus) = setup(&[0xE6, 0x10, 0xC6, 0x11]);
+        bus.write(0x0010, 0xFF);
+        bus.write(0x0011, 0x00);
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0010), 0x00);
+        assert!(cpu.get_flag(Flags::Zero));
+        assert!(!cpu.get_flag(Flags::Negative));
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0011), 0xFF);
+        assert!(!cpu.get_flag(Flags::Zero));
+        assert!(cpu.get_flag(Flags::Negative));
+    }
+
+    #[test]
+    fn jmp_indirect() {
+        // JMP ($0210)
+        let (mut cpu, mut bus) = setup(&[0x6C, 0x10, 0x02]);
+        bus.write(0x0210, 0xCD);
+        bus.write(0x0211, 0xAB);
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.pc, 0xABCD);
+    }
+
+    #[test]
+    fn countdown_loop() {
+        // LDX #$05 / loop: INY / DEX / BNE loop
+        let (mut cpu, mut bus) = setup(&[0xA2, 0x05, 0xC8, 0xCA, 0xD0, 0xFC]);
+
+        while cpu.pc != 0x0606 {
+            cpu.step(&mut bus);
+        }
+
+        assert_eq!(cpu.y, 5);
+        assert_eq!(cpu.x, 0);
+        assert!(cpu.get_flag(Flags::Zero));
+        assert!(!cpu.get_flag(Flags::Negative));
+        // LDX, 5 INY and DEX, 4 taken and 1 untaken BNE
+        assert_eq!(cpu.cycles(), 2 + 5 * 4 + 4 * 3 + 2);
+    }
+
+    #[test]
+    fn loads_every_mode() {
+        // (instruction, loaded value, cycles)
+        let cases: [(&[u8], u8, u8); 18] = [
+            (&[0xA9, 0x42], 0x42, 2),
+            (&[0xA5, 0x10], 0x11, 3),
+            (&[0xB5, 0x10], 0x22, 4),
+            (&[0xAD, 0x00, 0x04], 0x44, 4),
+            (&[0xBD, 0x00, 0x04], 0x55, 4),
+            (&[0xB9, 0x00, 0x04], 0x66, 4),
+            (&[0xA2, 0x42], 0x42, 2),
+            (&[0xA6, 0x10], 0x11, 3),
+            (&[0xB6, 0x10], 0x33, 4),
+            (&[0xAE, 0x00, 0x04], 0x44, 4),
+            (&[0xBE, 0x00, 0x04], 0x66, 4),
+            (&[0xBE, 0xFF, 0x04], 0x88, 5),
+            (&[0xA0, 0x42], 0x42, 2),
+            (&[0xA4, 0x10], 0x11, 3),
+            (&[0xB4, 0x10], 0x22, 4),
+            (&[0xAC, 0x00, 0x04], 0x44, 4),
+            (&[0xBC, 0x00, 0x04], 0x55, 4),
+            (&[0xBC, 0xFF, 0x04], 0x77, 5),
+        ];
+
+        for (program, val, cycles) in cases.iter() {
+            let (mut cpu, mut bus) = setup(program);
+            for &(addr, byte) in &[(0x0010, 0x11), (0x0012, 0x22), (0x0013, 0x33), (0x0400, 0x44),
+                (0x0402, 0x55), (0x0403, 0x66), (0x0501, 0x77), (0x0502, 0x88)] {
+                bus.write(addr, byte);
+            }
+            cpu.x = 0x02;
+            cpu.y = 0x03;
+
+            assert_eq!(cpu.step(&mut bus), *cycles, "{:02X}", program[0]);
+
+            let reg = match OPCODE_TABLE[program[0] as usize].mnemonic {
+                "LDA" => cpu.a,
+                "LDX" => cpu.x,
+                _ => cpu.y,
+            };
+            assert_eq!(reg, *val, "{:02X}", program[0]);
+        }
+    }
+
+    #[test]
+    fn lsr_accumulator_and_memory() {
+        // LSR A / LSR $0400
+        let (mut cpu, mut bus) = setup(&[0x4A, 0x4E, 0x00, 0x04]);
+        bus.write(0x0400, 0x82);
+        cpu.a = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.a, 0x00);
+        assert!(cpu.get_flag(Flags::Carry));
+        assert!(cpu.get_flag(Flags::Zero));
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(bus.read(0x0400), 0x41);
+        assert_eq!(cpu.a, 0x00);
+        assert!(!cpu.get_flag(Flags::Carry));
+        assert!(!cpu.get_flag(Flags::Zero));
+    }
+
+    #[test]
+    fn indexed_stores_across_a_page_keep_their_timing() {
+        // STA $04FF,Y / STA ($20),Y
+        let (mut cpu, mut bus) = setup(&[0x99, 0xFF, 0x04, 0x91, 0x20]);
+        bus.write(0x0020, 0xFF);
+        bus.write(0x0021, 0x04);
+        cpu.a = 0x42;
+        cpu.y = 0x02;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0501), 0x42);
+
+        cpu.a = 0x43;
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(bus.read(0x0501), 0x43);
+    }
+
+    #[test]
+    fn rol_ror_16_bit_shift() {
+        // CLC / ROL $10 / ROL $11 / ROR $11 / ROR $10
+        let (mut cpu, mut bus) = setup(&[0x18, 0x26, 0x10, 0x26, 0x11, 0x66, 0x11, 0x66, 0x10]);
+        bus.write(0x0010, 0x80);
+        bus.write(0x0011, 0x41);
+
+        for _ in 0..3 {
+            cpu.step(&mut bus);
+        }
+        assert_eq!(bus.read(0x0010), 0x00);
+        assert_eq!(bus.read(0x0011), 0x83);
+        assert!(!cpu.get_flag(Flags::Carry));
+        assert!(cpu.get_flag(Flags::Negative));
+
+        for _ in 0..2 {
+            cpu.step(&mut bus);
+        }
+        assert_eq!(bus.read(0x0010), 0x80);
+        assert_eq!(bus.read(0x0011), 0x41);
+        assert!(!cpu.get_flag(Flags::Carry));
+    }
+
+    #[test]
+    fn transfers_between_every_register() {
+        // LDA #$80 / TAX / TAY / LDX #$00 / TXA / LDY #$01 / TYA / TSX / LDX #$00 / TXS
+        let (mut cpu, mut bus) = setup(&[
+            0xA9, 0x80, 0xAA, 0xA8, 0xA2, 0x00, 0x8A, 0xA0, 0x01, 0x98, 0xBA, 0xA2, 0x00, 0x9A,
+        ]);
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.x, 0x80);
+        assert!(cpu.get_flag(Flags::Negative));
+        cpu.step(&mut bus);
+        assert_eq!(cpu.y, 0x80);
+        assert!(cpu.get_flag(Flags::Negative));
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x00);
+        assert!(cpu.get_flag(Flags::Zero));
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x01);
+        assert!(!cpu.get_flag(Flags::Zero));
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.x, 0xFD);
+        assert!(cpu.get_flag(Flags::Negative));
+
+        // TXS leaves the flags alone
+        cpu.step(&mut bus);
+        let status = cpu.s;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.sp, 0x00);
+        assert_eq!(cpu.s, status);
+    }
+
+    #[test]
+    fn ora_flags_and_indirect_y_page_cross() {
+        // ORA #$00 / ORA #$80 / ORA ($20),Y
+        let (mut cpu, mut bus) = setup(&[0x09, 0x00, 0x09, 0x80, 0x11, 0x20]);
+        bus.write(0x0020, 0xFF);
+        bus.write(0x0021, 0x04);
+        bus.write(0x0500, 0x01);
+        cpu.y = 0x01;
+
+        cpu.step(&mut bus);
+        assert!(cpu.get_flag(Flags::Zero));
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x80);
+        assert!(cpu.get_flag(Flags::Negative));
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.a, 0x81);
+    }
+
+    #[test]
+    fn plp_restores_pushed_flags() {
+        // PHP / LDA #$80 / PLP
+        let (mut cpu, mut bus) = setup(&[0x08, 0xA9, 0x80, 0x28]);
+        cpu.s = 0x42;
+
+        cpu.step(&mut bus);
+        cpu.set_flag(Flags::Carry, true);
+        cpu.set_flag(Flags::Interrupt, true);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.s, 0xC5);
+
+        cpu.step(&mut bus);
+        // Everything but Unused comes back as pushed
+        assert_eq!(cpu.s, 0x62);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn eor_adc_negates() {
+        // CLC / EOR #$FF / ADC #$01
+        for &(val, neg, carry) in &[(0x05u8, 0xFBu8, false), (0x80, 0x80, false), (0x00, 0x00, true)] {
+            let (mut cpu, mut bus) = setup(&[0x18, 0x49, 0xFF, 0x69, 0x01]);
+            cpu.a = val;
+
+            for _ in 0..3 {
+                cpu.step(&mut bus);
+            }
+
+            assert_eq!(cpu.a, neg);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry);
+        }
+    }
+
+    #[test]
+    fn cmp_against_0x40() {
+        // (operand, C, Z, N)
+        for &(byte, carry, zero, negative) in &[
+            (0x40u8, true, true, false),
+            (0x50, false, false, true),
+            (0x30, true, false, false),
+        ] {
+            // CMP $10
+            let (mut cpu, mut bus) = setup(&[0xC5, 0x10]);
+            bus.write(0x0010, byte);
+            cpu.a = 0x40;
+
+            cpu.step(&mut bus);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry);
+            assert_eq!(cpu.get_flag(Flags::Zero), zero);
+            assert_eq!(cpu.get_flag(Flags::Negative), negative);
+            assert_eq!(cpu.a, 0x40);
+        }
+    }
+
+    #[test]
+    fn inc_dec_absolute_x_wrap() {
+        // INC $0400,X / DEC $0401,X
+        let (mut cpu, mut bus) = setup(&[0xFE, 0x00, 0x04, 0xDE, 0x01, 0x04]);
+        bus.write(0x0401, 0xFF);
+        bus.write(0x0402, 0x00);
+        cpu.x = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x0401), 0x00);
+        assert!(cpu.get_flag(Flags::Zero));
+
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x0402), 0xFF);
+        assert!(cpu.get_flag(Flags::Negative));
+    }
+
+    #[test]
+    fn flag_instructions_touch_only_their_flag() {
+        // (opcode, flag, value)
+        let cases = [
+            (0x18u8, Flags::Carry as u8, false),
+            (0x38, Flags::Carry as u8, true),
+            (0x58, Flags::Interrupt as u8, false),
+            (0x78, Flags::Interrupt as u8, true),
+            (0xB8, Flags::Overflow as u8, false),
+            (0xD8, Flags::Decimal as u8, false),
+            (0xF8, Flags::Decimal as u8, true),
+        ];
+
+        for &(opcode, flag, val) in cases.iter() {
+            for &status in &[0x00u8, 0xFF] {
+                let (mut cpu, mut bus) = setup(&[opcode]);
+                cpu.s = status;
+
+                assert_eq!(cpu.step(&mut bus), 2);
+                let expected = if val { status | flag } else { status & !flag };
+                assert_eq!(cpu.s, expected, "{:02X}", opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn nops_consume_bytes_and_cycles() {
+        // (opcodes, length, cycles)
+        let cases: [(&[u8], u16, u8); 6] = [
+            (&[0xEA, 0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA], 1, 2),
+            (&[0x80, 0x82, 0x89, 0xC2, 0xE2], 2, 2),
+            (&[0x04, 0x44, 0x64], 2, 3),
+            (&[0x14, 0x34, 0x54, 0x74, 0xD4, 0xF4], 2, 4),
+            (&[0x0C], 3, 4),
+            (&[0x1C, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC], 3, 4),
+        ];
+
+        for (opcodes, len, cycles) in cases.iter() {
+            for &opcode in opcodes.iter() {
+                let (mut cpu, mut bus) = setup(&[opcode, 0x00, 0x05]);
+                let (a, x, y, s, sp) = (cpu.a, cpu.x, cpu.y, cpu.s, cpu.sp);
+
+                assert_eq!(cpu.step(&mut bus), *cycles, "{:02X}", opcode);
+                assert_eq!(cpu.pc, 0x0600 + len, "{:02X}", opcode);
+                assert_eq!((cpu.a, cpu.x, cpu.y, cpu.s, cpu.sp), (a, x, y, s, sp));
+            }
+        }
+
+        // The absolute,X NOPs take the page-cross penalty
+        for &opcode in &[0x1Cu8, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC] {
+            let (mut cpu, mut bus) = setup(&[opcode, 0xFF, 0x05]);
+            cpu.x = 0x01;
+
+            assert_eq!(cpu.step(&mut bus), 5, "{:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn register_increments_wrap() {
+        // DEX / INY
+        let (mut cpu, mut bus) = setup(&[0xCA, 0xC8]);
+        cpu.x = 0x00;
+        cpu.y = 0xFF;
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.x, 0xFF);
+        assert!(cpu.get_flag(Flags::Negative));
+        assert!(!cpu.get_flag(Flags::Zero));
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.y, 0x00);
+        assert!(!cpu.get_flag(Flags::Negative));
+        assert!(cpu.get_flag(Flags::Zero));
+    }
+
+    #[test]
+    fn eor_and_ora() {
+        // (opcode, A, operand, result)
+        let cases = [
+            (0x09u8, 0x5Au8, 0x00u8, 0x5Au8),
+            (0x09, 0x0F, 0xF0, 0xFF),
+            (0x09, 0x12, 0x21, 0x33),
+            (0x49, 0x5A, 0xFF, 0xA5),
+            (0x49, 0xFF, 0xFF, 0x00),
+            (0x49, 0x0F, 0x3C, 0x33),
+        ];
+
+        for &(opcode, a, byte, res) in cases.iter() {
+            let (mut cpu, mut bus) = setup(&[opcode, byte]);
+            cpu.a = a;
+
+            cpu.step(&mut bus);
+            assert_eq!(cpu.a, res, "{:02X} {:02X} {:02X}", opcode, a, byte);
+            assert_eq!(cpu.get_flag(Flags::Zero), res == 0);
+            assert_eq!(cpu.get_flag(Flags::Negative), res & 0x80 != 0);
+        }
+    }
+
+    #[test]
+    fn bpl_bvc_bvs() {
+        // (opcode, status that takes the branch, status that doesn't)
+        let cases = [(0x10u8, 0x00u8, 0x80u8), (0x50, 0x00, 0x40), (0x70, 0x40, 0x00)];
+
+        // (branch address, offset, target when taken, cycles when taken)
+        let targets = [
+            (0x0640u16, 0x10u8, 0x0652u16, 3u8),
+            (0x0640, 0xF0, 0x0632, 3),
+            (0x06F0, 0x20, 0x0712, 4),
+            (0x0600, 0xF0, 0x05F2, 4),
+        ];
+
+        for &(opcode, taken, not_taken) in cases.iter() {
+            for &(addr, offset, target, cycles) in targets.iter() {
+                let (mut cpu, mut bus) = setup(&[]);
+                bus.write(addr, opcode);
+                bus.write(addr + 1, offset);
+
+                cpu.pc = addr;
+                cpu.s = taken;
+                assert_eq!(cpu.step(&mut bus), cycles, "{:02X} {:04X}", opcode, addr);
+                assert_eq!(cpu.pc, target);
+
+                cpu.pc = addr;
+                cpu.s = not_taken;
+                assert_eq!(cpu.step(&mut bus), 2, "{:02X} {:04X}", opcode, addr);
+                assert_eq!(cpu.pc, addr + 2);
+            }
+        }
+    }
+
+    #[test]
+    fn lsr_zero_page() {
+        // LSR $10 / LSR $10,X
+        let (mut cpu, mut bus) = setup(&[0x46, 0x10, 0x56, 0x10]);
+        bus.write(0x0010, 0x03);
+        bus.write(0x0011, 0x80);
+        cpu.a = 0x55;
+        cpu.x = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0010), 0x01);
+        assert!(cpu.get_flag(Flags::Carry));
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(bus.read(0x0011), 0x40);
+        assert!(!cpu.get_flag(Flags::Carry));
+        assert!(!cpu.get_flag(Flags::Negative));
+        assert_eq!(cpu.a, 0x55);
+    }
+
+    #[test]
+    fn lax_loads_a_and_x() {
+        // LAX $04FF,Y / LAX ($20),Y
+        let (mut cpu, mut bus) = setup(&[0xBF, 0xFF, 0x04, 0xB3, 0x20]);
+        bus.write(0x0500, 0x80);
+        bus.write(0x0020, 0x00);
+        bus.write(0x0021, 0x04);
+        bus.write(0x0401, 0x00);
+        cpu.y = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!((cpu.a, cpu.x), (0x80, 0x80));
+        assert!(cpu.get_flag(Flags::Negative));
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!((cpu.a, cpu.x), (0x00, 0x00));
+        assert!(cpu.get_flag(Flags::Zero));
+    }
+
+    #[test]
+    fn sax_stores_a_and_x() {
+        // SAX $10 / SAX $10,Y
+        let (mut cpu, mut bus) = setup(&[0x87, 0x10, 0x97, 0x10]);
+        cpu.a = 0xF0;
+        cpu.x = 0x3C;
+        cpu.y = 0x02;
+        cpu.s = 0x00;
+
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(bus.read(0x0010), 0x30);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(bus.read(0x0012), 0x30);
+        assert_eq!(cpu.s, 0x00);
+    }
+
+    #[test]
+    fn rotate_a_bit_through_carry() {
+        // ROL A nine times, then ROR A nine times
+        let (mut cpu, mut bus) = setup(&[0x2A; 9]);
+        for i in 0..9 {
+            bus.write(0x0609 + i, 0x6A);
+        }
+        cpu.a = 0x01;
+
+        for i in 1..=8 {
+            cpu.step(&mut bus);
+            assert_eq!(cpu.a, (0x01u16 << i) as u8);
+        }
+        assert!(cpu.get_flag(Flags::Carry));
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x01);
+        assert!(!cpu.get_flag(Flags::Carry));
+
+        for _ in 0..9 {
+            cpu.step(&mut bus);
+        }
+        assert_eq!(cpu.a, 0x01);
+        assert!(!cpu.get_flag(Flags::Carry));
+    }
+
+    #[test]
+    fn rmw_combos() {
+        // (opcode, A, memory, carry in, memory after, A after, carry out)
+        let cases = [
+            (0x07u8, 0x10u8, 0x81u8, false, 0x02u8, 0x12u8, true),
+            (0x27, 0xFF, 0x81, true, 0x03, 0x03, true),
+            (0x47, 0xFF, 0x03, false, 0x01, 0xFE, true),
+            (0x67, 0x10, 0x02, true, 0x81, 0x91, false),
+            (0xC7, 0x40, 0x41, false, 0x40, 0x40, true),
+            (0xE7, 0x20, 0x0F, true, 0x10, 0x10, true),
+        ];
+
+        for &(opcode, a, byte, carry, res, a_res, carry_res) in cases.iter() {
+            let (mut cpu, mut bus) = setup(&[opcode, 0x10]);
+            bus.write(0x0010, byte);
+            cpu.a = a;
+            cpu.s = carry as u8;
+
+            assert_eq!(cpu.step(&mut bus), 5, "{:02X}", opcode);
+            assert_eq!(bus.read(0x0010), res, "{:02X}", opcode);
+            assert_eq!(cpu.a, a_res, "{:02X}", opcode);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry_res, "{:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn sbc_overflow_table() {
+        // (A, operand, result, C, V), carry set going in
+        let cases = [
+            (0x50u8, 0xF0u8, 0x60u8, false, false),
+            (0x50, 0xB0, 0xA0, false, true),
+            (0x50, 0x70, 0xE0, false, false),
+            (0x50, 0x30, 0x20, true, false),
+            (0xD0, 0xF0, 0xE0, false, false),
+            (0xD0, 0xB0, 0x20, true, false),
+            (0xD0, 0x70, 0x60, true, true),
+            (0xD0, 0x30, 0xA0, true, false),
+        ];
+
+        for &(a, byte, res, carry, overflow) in cases.iter() {
+            // SBC #
+            let (mut cpu, mut bus) = setup(&[0xE9, byte]);
+            cpu.a = a;
+            cpu.s = Flags::Carry as u8;
+
+            cpu.step(&mut bus);
+            assert_eq!(cpu.a, res, "{:02X} - {:02X}", a, byte);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry, "{:02X} - {:02X}", a, byte);
+            assert_eq!(cpu.get_flag(Flags::Overflow), overflow, "{:02X} - {:02X}", a, byte);
+        }
+    }
+
+    #[test]
+    fn immediate_illegals() {
+        // (opcode, operand, A, X, carry in, A after, X after, C, V)
+        let cases = [
+            (0x0Bu8, 0x80u8, 0xFFu8, 0x00u8, false, 0x80u8, 0x00u8, true, false),
+            (0x2B, 0x0F, 0xFF, 0x00, true, 0x0F, 0x00, false, false),
+            (0x4B, 0x03, 0xFF, 0x00, false, 0x01, 0x00, true, false),
+            (0x6B, 0xFF, 0xFF, 0x00, true, 0xFF, 0x00, true, false),
+            (0x6B, 0x40, 0xFF, 0x00, false, 0x20, 0x00, false, true),
+            (0xCB, 0x02, 0xFF, 0x0F, false, 0xFF, 0x0D, true, false),
+            (0xCB, 0x10, 0xFF, 0x0F, true, 0xFF, 0xFF, false, false),
+        ];
+
+        for &(opcode, byte, a, x, carry, a_res, x_res, carry_res, overflow) in cases.iter() {
+            let (mut cpu, mut bus) = setup(&[opcode, byte]);
+            cpu.a = a;
+            cpu.x = x;
+            cpu.s = carry as u8;
+
+            assert_eq!(cpu.step(&mut bus), 2);
+            assert_eq!((cpu.a, cpu.x), (a_res, x_res), "{:02X} #{:02X}", opcode, byte);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry_res, "{:02X} #{:02X}", opcode, byte);
+            assert_eq!(cpu.get_flag(Flags::Overflow), overflow, "{:02X} #{:02X}", opcode, byte);
+        }
+    }
+
+    #[test]
+    fn shy_absolute_x_in_every_mode() {
+        // SHY $0500,X
+        let program = [0x9C, 0x00, 0x05];
+
+        let (mut cpu, mut bus) = setup(&program);
+        cpu.x = 0x01;
+        cpu.y = 0xFF;
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0501), 0x06);
+        assert_eq!(cpu.pc, 0x0603);
+
+        let (mut cpu, mut bus) = setup(&program);
+        cpu.set_unstable_opcode_mode(UnstableMode::TreatAsNop);
+        cpu.x = 0x01;
+        cpu.y = 0xFF;
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0501), 0x00);
+        assert_eq!(cpu.pc, 0x0603);
+
+        let (mut cpu, mut bus) = setup(&program);
+        cpu.set_unstable_opcode_mode(UnstableMode::Error);
+        cpu.x = 0x01;
+        cpu.y = 0xFF;
+        assert_eq!(
+            cpu.try_step(&mut bus),
+            Err(EmuError::UnstableOpcode { opcode: 0x9C, addr: 0x0600 })
+        );
+        assert_eq!(bus.read(0x0501), 0x00);
+        assert_eq!(cpu.pc, 0x0600);
+        assert!(!cpu.is_jammed());
+    }
+
+    #[test]
+    fn shy_page_cross_replaces_the_high_byte() {
+        // SHY $05FF,X
+        let (mut cpu, mut bus) = setup(&[0x9C, 0xFF, 0x05]);
+        cpu.x = 0x01;
+        cpu.y = 0x03;
+
+        cpu.step(&mut bus);
+        // Y & ($05 + 1) = $02, which also becomes the high byte
+        assert_eq!(bus.read(0x0200), 0x02);
+        assert_eq!(bus.read(0x0600), 0x9C);
+    }
+
+    #[test]
+    fn lxa_loads_a_and_x() {
+        // LXA #$5F
+        let (mut cpu, mut bus) = setup(&[0xAB, 0x5F]);
+        cpu.a = 0x00;
+        cpu.x = 0x12;
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!((cpu.a, cpu.x), (0x4E, 0x4E));
+        assert!(!cpu.get_flag(Flags::Zero));
+
+        let (mut cpu, mut bus) = setup(&[0xAB, 0x5F]);
+        cpu.set_unstable_opcode_mode(UnstableMode::TreatAsNop);
+        cpu.x = 0x12;
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!((cpu.a, cpu.x), (0x00, 0x12));
+        assert_eq!(cpu.pc, 0x0602);
+    }
+
+    #[test]
+    fn toggle_flags_and_read_back_status() {
+        // SEC / SED / SEI / BIT $10 / PHP / CLC / CLD / CLI / CLV / PHP
+        let (mut cpu, mut bus) = setup(&[
+            0x38, 0xF8, 0x78, 0x24, 0x10, 0x08, 0x18, 0xD8, 0x58, 0xB8, 0x08,
+        ]);
+        bus.write(0x0010, 0x40);
+        cpu.a = 0x40;
+
+        for _ in 0..10 {
+            cpu.step(&mut bus);
+        }
+
+        // C, D, I and V set, plus Break and Unused in the pushed copy
+        assert_eq!(bus.read(0x01FD), 0x7D);
+        assert_eq!(bus.read(0x01FC), 0x30);
+        assert_eq!(cpu.s, 0x00);
+    }
+
+    #[test]
+    fn jam_halts_until_reset() {
+        // JAM
+        let (mut cpu, mut bus) = setup(&[0x02]);
+        bus.write(0x0700, 0xEA);
+        bus.write(0xFFFC, 0x00);
+        bus.write(0xFFFD, 0x07);
+
+        assert_eq!(cpu.step(&mut bus), 0);
+        assert!(cpu.is_jammed());
+        assert_eq!(cpu.pc, 0x0600);
+
+        assert_eq!(cpu.step(&mut bus), 0);
+        assert_eq!(cpu.pc, 0x0600);
+        assert_eq!(cpu.try_step(&mut bus), Err(EmuError::Jammed(0x0600)));
+
+        cpu.reset(&mut bus);
+        assert!(!cpu.is_jammed());
+        assert_eq!(cpu.try_step(&mut bus), Ok(2));
+        assert_eq!(cpu.pc, 0x0701);
+    }
+
+    #[test]
+    fn every_jam_opcode_halts() {
+        for &opcode in &[0x02u8, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2] {
+            let (mut cpu, mut bus) = setup(&[opcode]);
+
+            assert_eq!(cpu.step(&mut bus), 0);
+            assert!(cpu.is_jammed(), "{:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn step_runs_a_loop_until_the_cpu_halts() {
+        // LDX #$03 / loop: DEX / BNE loop / JAM
+        let (mut cpu, mut bus) = setup(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0x02]);
+
+        let mut steps = 0;
+        while cpu.step(&mut bus) != 0 {
+            steps += 1;
+        }
+
+        // LDX, 3 DEX and 3 BNE
+        assert_eq!(steps, 7);
+        assert_eq!(cpu.x, 0);
+        assert!(cpu.is_jammed());
+        assert_eq!(cpu.pc, 0x0605);
+    }
+
+    #[test]
+    fn bpl_bvc_bvs_follow_the_flags_set_by_code() {
+        // LDA #$01 / BPL +1 / JAM / BIT $10 / BVC +1 / BVS +1 / JAM / NOP
+        let (mut cpu, mut bus) = setup(&[
+            0xA9, 0x01, 0x10, 0x01, 0x02, 0x24, 0x10, 0x50, 0x01, 0x70, 0x01, 0x02, 0xEA,
+        ]);
+        bus.write(0x0010, 0x40);
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.pc, 0x0605);
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x0609);
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.pc, 0x060C);
+        assert!(!cpu.is_jammed());
+    }
+
+    #[test]
+    fn nop_table_entries_match_execution() {
+        let mut count = 0;
+
+        for opcode in 0..=255u8 {
+            let info = OPCODE_TABLE[opcode as usize];
+            if info.mnemonic != "NOP" {
+                continue;
+            }
+            count += 1;
+
+            let (mut cpu, mut bus) = setup(&[opcode, 0x10, 0x02]);
+            assert_eq!(cpu.step(&mut bus), info.cycles, "{:02X}", opcode);
+            assert_eq!(cpu.pc - 0x0600, info.len as u16, "{:02X}", opcode);
+        }
+
+        assert_eq!(count, 28);
+    }
+
+    #[test]
+    fn opcode_table_lengths() {
+        // LDA #$01 / STA $0200 / INX / BNE -6 / JMP ($1000)
+        let program = [0xA9, 0x01, 0x8D, 0x00, 0x02, 0xE8, 0xD0, 0xFA, 0x6C, 0x00, 0x10];
+
+        let mut pos = 0;
+        let mut count = 0;
+        while pos < program.len() {
+            pos += OPCODE_TABLE[program[pos] as usize].len as usize;
+            count += 1;
+        }
+
+        assert_eq!(pos, 11);
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn opcode_table_is_complete() {
+        for (opcode, info) in OPCODE_TABLE.iter().enumerate() {
+            assert_ne!(info.mnemonic, "???", "{:02X}", opcode);
+            assert_eq!(info.len, info.mode.len(), "{:02X}", opcode);
+        }
+
+        assert_eq!(OPCODE_TABLE[0xAB].mnemonic, "LXA");
+        assert_eq!(OPCODE_TABLE[0xAB].len, 2);
+    }
+
+    #[test]
+    fn resolve_every_addressing_mode() {
+        type M = AddressingMode;
+
+        let cases = [
+            (M::Implied, Operand::Implied),
+            (M::Accumulator, Operand::Accumulator),
+            (M::Immediate, Operand::Immediate(0x10)),
+            (M::Relative, Operand::Relative(0x0010)),
+            (M::ZeroPage, Operand::Address(0x0010, false)),
+            (M::ZeroPageX, Operand::Address(0x0011, false)),
+            (M::ZeroPageY, Operand::Address(0x0012, false)),
+            (M::Absolute, Operand::Address(0x0510, false)),
+            (M::AbsoluteX, Operand::Address(0x0511, false)),
+            (M::AbsoluteY, Operand::Address(0x0512, false)),
+            (M::Indirect, Operand::Address(0x1234, false)),
+            (M::IndexedIndirect, Operand::Address(0x0300, false)),
+            (M::IndirectIndexed, Operand::Address(0x0082, false)),
+        ];
+
+        for &(mode, operand) in cases.iter() {
+            let (mut cpu, mut bus) = setup(&[0x10, 0x05]);
+            bus.write(0x0010, 0x80);
+            bus.write(0x0011, 0x00);
+            bus.write(0x0012, 0x03);
+            bus.write(0x0510, 0x34);
+            bus.write(0x0511, 0x12);
+            cpu.x = 0x01;
+            cpu.y = 0x02;
+
+            assert_eq!(cpu.resolve(mode, &mut bus), operand, "{:?}", mode);
+            assert_eq!(cpu.pc, 0x0600 + mode.len() as u16 - 1, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn shift_combos_in_every_mode() {
+        // (SLO opcode and operand, target address, cycles)
+        let modes: [([u8; 3], u16, u8); 7] = [
+            ([0x07, 0x10, 0x00], 0x0010, 5),
+            ([0x17, 0x10, 0x00], 0x0011, 6),
+            ([0x0F, 0x00, 0x04], 0x0400, 6),
+            ([0x1F, 0x00, 0x04], 0x0401, 7),
+            ([0x1B, 0x00, 0x04], 0x0402, 7),
+            ([0x03, 0x1F, 0x00], 0x0400, 8),
+            ([0x13, 0x20, 0x00], 0x0402, 8),
+        ];
+
+        // (offset from SLO, memory after, A after) for $40 in memory and A = $01
+        let ops = [(0x00u8, 0x80u8, 0x81u8), (0x20, 0x80, 0x00), (0x40, 0x20, 0x21), (0x60, 0x20, 0x21)];
+
+        for &(offset, res, a_res) in ops.iter() {
+            for &(mut program, addr, cycles) in modes.iter() {
+                program[0] += offset;
+
+                let (mut cpu, mut bus) = setup(&program);
+                bus.write(0x0020, 0x00);
+                bus.write(0x0021, 0x04);
+                bus.write(addr, 0x40);
+                cpu.a = 0x01;
+                cpu.x = 0x01;
+                cpu.y = 0x02;
+
+                assert_eq!(cpu.step(&mut bus), cycles, "{:02X}", program[0]);
+                assert_eq!(bus.read(addr), res, "{:02X}", program[0]);
+                assert_eq!(cpu.a, a_res, "{:02X}", program[0]);
+                assert!(!cpu.get_flag(Flags::Carry));
+            }
+        }
+    }
+
+    #[test]
+    fn adc_in_every_mode() {
+        // CLC / ADC #$01 / ADC $10 / ADC $10,X / ADC $0400 / ADC $0400,X
+        // ADC $0400,Y / ADC ($1F,X) / ADC ($22),Y
+        let (mut cpu, mut bus) = setup(&[
+            0x18, 0x69, 0x01, 0x65, 0x10, 0x75, 0x10, 0x6D, 0x00, 0x04, 0x7D, 0x00, 0x04,
+            0x79, 0x00, 0x04, 0x61, 0x1F, 0x71, 0x22,
+        ]);
+        for &(addr, byte) in &[
+            (0x0010, 0x02),
+            (0x0011, 0x04),
+            (0x0400, 0x08),
+            (0x0401, 0x10),
+            (0x0402, 0x20),
+            (0x0020, 0x10),
+            (0x0021, 0x04),
+            (0x0410, 0x40),
+            (0x0022, 0x20),
+            (0x0023, 0x04),
+            (0x0422, 0x80),
+        ] {
+            bus.write(addr, byte);
+        }
+        cpu.x = 0x01;
+        cpu.y = 0x02;
+
+        for _ in 0..9 {
+            cpu.step(&mut bus);
+        }
+
+        assert_eq!(cpu.a, 0xFF);
+        assert_eq!(cpu.pc, 0x0614);
+        assert_eq!(cpu.cycles(), 2 + 2 + 3 + 4 + 4 + 4 + 4 + 6 + 5);
+        assert!(!cpu.get_flag(Flags::Carry));
+        assert!(cpu.get_flag(Flags::Negative));
+    }
+
+    #[test]
+    fn dcp_and_isc() {
+        // DCP $10 wrapping $00 to $FF borrows
+        let (mut cpu, mut bus) = setup(&[0xC7, 0x10]);
+        bus.write(0x0010, 0x00);
+        cpu.a = 0x10;
+
+        cpu.step(&mut bus);
+        assert_eq!(bus.read(0x0010), 0xFF);
+        assert_eq!(cpu.a, 0x10);
+        assert!(!cpu.get_flag(Flags::Carry));
+        assert!(!cpu.get_flag(Flags::Zero));
+        assert!(!cpu.get_flag(Flags::Negative));
+
+        // (memory, A, memory after, A after, C, N), carry set going in
+        let cases = [(0xFFu8, 0x05u8, 0x00u8, 0x05u8, true, false), (0x05, 0x05, 0x06, 0xFF, false, true)];
+
+        for &(byte, a, res, a_res, carry, negative) in cases.iter() {
+            // ISC $0400,Y
+            let (mut cpu, mut bus) = setup(&[0xFB, 0x00, 0x04]);
+            bus.write(0x0401, byte);
+            cpu.a = a;
+            cpu.y = 0x01;
+            cpu.s = Flags::Carry as u8;
+
+            assert_eq!(cpu.step(&mut bus), 7);
+            assert_eq!(bus.read(0x0401), res);
+            assert_eq!(cpu.a, a_res);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry);
+            assert_eq!(cpu.get_flag(Flags::Negative), negative);
+        }
+    }
+
+    #[test]
+    fn implied_opcodes_run_through_step() {
+        // NOP / CLC
+        let (mut cpu, mut bus) = setup(&[0xEA, 0x18]);
+        cpu.s = Flags::Carry as u8;
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x0601);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x0602);
+        assert!(!cpu.get_flag(Flags::Carry));
+    }
+
+    #[test]
+    fn arr_carry_and_overflow() {
+        // (operand, result, C, V) with A = $FF and carry clear
+        let cases = [
+            (0x00u8, 0x00u8, false, false),
+            (0x40, 0x20, false, true),
+            (0x80, 0x40, true, true),
+            (0xC0, 0x60, true, false),
+        ];
+
+        for &(byte, res, carry, overflow) in cases.iter() {
+            // ARR #
+            let (mut cpu, mut bus) = setup(&[0x6B, byte]);
+            cpu.a = 0xFF;
+
+            cpu.step(&mut bus);
+            assert_eq!(cpu.a, res, "#{:02X}", byte);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry, "#{:02X}", byte);
+            assert_eq!(cpu.get_flag(Flags::Overflow), overflow, "#{:02X}", byte);
+            assert_eq!(cpu.get_flag(Flags::Zero), res == 0, "#{:02X}", byte);
+        }
+    }
+
+    #[test]
+    fn anc_alr_sbx_zero_results() {
+        // ANC #$00 / ALR #$01 / SBX #$30
+        let (mut cpu, mut bus) = setup(&[0x0B, 0x00, 0x4B, 0x01, 0xCB, 0x30]);
+
+        cpu.a = 0xFF;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x00);
+        assert!(cpu.get_flag(Flags::Zero));
+        assert!(!cpu.get_flag(Flags::Carry));
+
+        cpu.a = 0xFF;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x00);
+        assert!(cpu.get_flag(Flags::Zero));
+        assert!(cpu.get_flag(Flags::Carry));
+
+        cpu.a = 0xF0;
+        cpu.x = 0x3C;
+        cpu.s = 0x00;
+        cpu.step(&mut bus);
+        assert_eq!((cpu.a, cpu.x), (0xF0, 0x00));
+        assert!(cpu.get_flag(Flags::Zero));
+        assert!(cpu.get_flag(Flags::Carry));
+    }
+
+    // A memory access, as recorded by RecordingBus
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Access {
+        Read(u16, u8),
+        Write(u16, u8),
+    }
+
+    // Flat 64KB of memory that records every access the CPU makes
+    struct RecordingBus {
+        ram: Vec<u8>,
+        log: Vec<Access>,
+    }
+
+    impl CpuBus for RecordingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            let byte = self.ram[addr as usize];
+            self.log.push(Access::Read(addr, byte));
+            byte
+        }
+
+        fn write(&mut self, addr: u16, byte: u8) {
+            self.log.push(Access::Write(addr, byte));
+            self.ram[addr as usize] = byte;
+        }
+    }
+
+    // Like setup(), but on a RecordingBus with an empty log
+    fn setup_recording(program: &[u8]) -> (MOS6502, RecordingBus) {
+        let mut ram = vec![0; 0x10000];
+        ram[0x0600..0x0600 + program.len()].copy_from_slice(program);
+
+        let mut cpu = MOS6502::new();
+        cpu.pc = 0x0600;
+
+        (cpu, RecordingBus { ram, log: Vec::new() })
+    }
+
+    #[test]
+    fn rmw_writes_the_old_value_back_first() {
+        // INC $10
+        let (mut cpu, mut bus) = setup_recording(&[0xE6, 0x10]);
+        bus.ram[0x0010] = 0x41;
+
+        cpu.step(&mut bus);
+        assert_eq!(
+            bus.log,
+            [
+                Access::Read(0x0600, 0xE6),
+                Access::Read(0x0601, 0x10),
+                Access::Read(0x0010, 0x41),
+                Access::Write(0x0010, 0x41),
+                Access::Write(0x0010, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_rmw_instruction_writes_twice() {
+        // ASL, LSR, ROL, ROR, INC, DEC, SLO, RLA, SRE, RRA, DCP and ISC on $10
+        for &opcode in &[0x06u8, 0x46, 0x26, 0x66, 0xE6, 0xC6, 0x07, 0x27, 0x47, 0x67, 0xC7, 0xE7] {
+            let (mut cpu, mut bus) = setup_recording(&[opcode, 0x10]);
+            bus.ram[0x0010] = 0x81;
+
+            cpu.step(&mut bus);
+
+            let writes: Vec<_> = bus.log.iter().filter(|a| matches!(a, Access::Write(..))).collect();
+            assert_eq!(writes.len(), 2, "{:02X}", opcode);
+            assert_eq!(*writes[0], Access::Write(0x0010, 0x81), "{:02X}", opcode);
+            assert_ne!(*writes[1], Access::Write(0x0010, 0x81), "{:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn implied_addressing_leaves_pc_alone() {
+        let (mut cpu, mut bus) = setup_recording(&[0xEA, 0xEA]);
+
+        assert_eq!(cpu.addr_implied(&mut bus), Operand::Implied);
+        assert_eq!(cpu.pc, 0x0600);
+        assert!(bus.log.is_empty());
+
+        // CLC
+        bus.ram[0x0600] = 0x18;
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x0601);
+    }
+
+    #[test]
+    fn indexed_dummy_reads() {
+        // (instruction, address, reads of the address)
+        let cases = [
+            // LDA $0400,X stays on the page, no dummy read
+            ([0xBD, 0x00, 0x04], 0x0401, 1),
+            // LDA $04FF,X reads $0400 before the high byte is fixed
+            ([0xBD, 0xFF, 0x04], 0x0400, 1),
+            ([0xBD, 0xFF, 0x04], 0x0500, 1),
+            // LDA ($20),Y crossing from $04FF
+            ([0xB1, 0x20, 0x00], 0x0400, 1),
+            ([0xB1, 0x20, 0x00], 0x0500, 1),
+            // STA $0400,X always reads first
+            ([0x9D, 0x00, 0x04], 0x0401, 1),
+            ([0x9D, 0xFF, 0x04], 0x0400, 1),
+            ([0x9D, 0xFF, 0x04], 0x0500, 0),
+            // INC $0400,X reads the target twice
+            ([0xFE, 0x00, 0x04], 0x0401, 2),
+            // LDA $0400 isn't indexed
+            ([0xAD, 0x00, 0x04], 0x0400, 1),
+            ([0xAD, 0x00, 0x04], 0x0300, 0),
+        ];
+
+        for &(program, addr, count) in cases.iter() {
+            let (mut cpu, mut bus) = setup_recording(&program);
+            bus.ram[0x0020] = 0xFE;
+            bus.ram[0x0021] = 0x04;
+            cpu.x = 0x01;
+            cpu.y = 0x02;
+
+            cpu.step(&mut bus);
+
+            let reads = bus.log.iter().filter(|a| matches!(a, Access::Read(r, _) if *r == addr)).count();
+            assert_eq!(reads, count, "{:02X} ${:04X}", program[0], addr);
+        }
+    }
+
+    #[test]
+    fn cycle_reference_table() {
+        // Base cycles of every opcode, JAMs take none
+        #[rustfmt::skip]
+        const CYCLES: [u8; 256] = [
+            7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6,
+            2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+            6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6,
+            2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+            6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6,
+            2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+            6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
+            2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+            2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+            2, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
+            2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+            2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
+            2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+            2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+            2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+            2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+        ];
+
+        // Opcodes taking an extra cycle when indexing crosses a page
+        const PENALTY: [u8; 32] = [
+            0x11, 0x19, 0x1D, 0x31, 0x39, 0x3D, 0x51, 0x59, 0x5D, 0x71, 0x79, 0x7D,
+            0xB1, 0xB9, 0xBD, 0xD1, 0xD9, 0xDD, 0xF1, 0xF9, 0xFD, 0xBC, 0xBE, 0xB3,
+            0xBF, 0xBB, 0x1C, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC,
+        ];
+
+        for &index in &[0x00u8, 0xFF] {
+            for opcode in 0..=255u8 {
+                // Operand $0001, also the pointer $0001 for the indirect modes
+                let (mut cpu, mut bus) = setup(&[opcode, 0x01, 0x00]);
+                bus.write(0x0001, 0x01);
+                cpu.x = index;
+                cpu.y = index;
+
+                // Branches are never taken
+                cpu.s = if matches!(opcode, 0x10 | 0x50 | 0x90 | 0xD0) { 0xFF } else { 0x00 };
+
+                // Indexing by $FF crosses a page
+                let crossed = index == 0xFF && PENALTY.contains(&opcode);
+                let expected = CYCLES[opcode as usize] + crossed as u8;
+
+                assert_eq!(cpu.step(&mut bus), expected, "{:02X} indexed by {:02X}", opcode, index);
+            }
+        }
+    }
+
+    #[test]
+    fn negative_set_on_exactly_0x80() {
+        // LDA #$80 / LDA #$00 / ADC #$80 / LDA #$00 / BIT $10 / LDA #$00 / CMP #$80
+        let (mut cpu, mut bus) = setup(&[
+            0xA9, 0x80, 0xA9, 0x00, 0x69, 0x80, 0xA9, 0x00, 0x24, 0x10, 0xA9, 0x00, 0xC9, 0x80,
+        ]);
+        bus.write(0x0010, 0x80);
+
+        for i in 0..7 {
+            cpu.step(&mut bus);
+            assert_eq!(cpu.get_flag(Flags::Negative), i % 2 == 0, "instruction {}", i);
+        }
+    }
+
+    #[test]
+    fn adc_overflow_cases() {
+        // (A, operand, result, C, V), carry clear going in
+        let cases = [
+            (0x50u8, 0x10u8, 0x60u8, false, false),
+            (0x50, 0x50, 0xA0, false, true),
+            (0xD0, 0x90, 0x60, true, true),
+            (0xD0, 0xD0, 0xA0, true, false),
+        ];
+
+        for &(a, byte, res, carry, overflow) in cases.iter() {
+            // ADC #
+            let (mut cpu, mut bus) = setup(&[0x69, byte]);
+            cpu.a = a;
+
+            cpu.step(&mut bus);
+            assert_eq!(cpu.a, res, "{:02X} + {:02X}", a, byte);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry, "{:02X} + {:02X}", a, byte);
+            assert_eq!(cpu.get_flag(Flags::Overflow), overflow, "{:02X} + {:02X}", a, byte);
+        }
+    }
+
+    #[test]
+    fn taken_branch_still_sees_an_irq_raised_before_it() {
+        // BNE +0 / NOP, the IRQ line is already up
+        let (mut cpu, mut bus) = setup(&[0xD0, 0x00, 0xEA]);
+        bus.write(0xFFFE, 0x00);
+        bus.write(0xFFFF, 0x90);
+        cpu.set_irq(true);
+
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(bus.read(0x01FC), 0x02);
+    }
+
+    // Load a program at $0600 with an IRQ handler at $9000 and the IRQ
+    // line held active
+    fn setup_irq(program: &[u8], s: u8) -> (MOS6502, Bus) {
+        let (mut cpu, mut bus) = setup(program);
+        bus.write(0xFFFE, 0x00);
+        bus.write(0xFFFF, 0x90);
+
+        cpu.s = s;
+        cpu.set_irq(true);
+
+        (cpu, bus)
+    }
+
+    #[test]
+    fn cli_delays_irq_by_one_instruction() {
+        // CLI / NOP / NOP
+        let (mut cpu, mut bus) = setup_irq(&[0x58, 0xEA, 0xEA], Flags::Interrupt as u8);
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x0602);
+
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(bus.read(0x01FC), 0x02);
+    }
+
+    #[test]
+    fn sei_lets_one_irq_through() {
+        // SEI / NOP
+        let (mut cpu, mut bus) = setup_irq(&[0x78, 0xEA], 0x00);
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(bus.read(0x01FC), 0x01);
+        // The pushed status already has Interrupt Disable set
+        assert_eq!(bus.read(0x01FB), 0x24);
+    }
+
+    #[test]
+    fn plp_changes_interrupt_disable_after_the_poll() {
+        // PLP / NOP / NOP
+        let program = [0x28, 0xEA, 0xEA];
+
+        // Setting the flag lets one IRQ through
+        let (mut cpu, mut bus) = setup_irq(&program, 0x00);
+        cpu.stack_push(Flags::Interrupt as u8, &mut bus);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x01FC), 0x01);
+
+        // Clearing it delays the IRQ by one instruction
+        let (mut cpu, mut bus) = setup_irq(&program, Flags::Interrupt as u8);
+        cpu.stack_push(0x00, &mut bus);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x01FC), 0x02);
+    }
+
+    #[test]
+    fn bit_copies_bits_7_and_6() {
+        // Operand, Zero, Negative, Overflow with A = $0F
+        let cases = [
+            (0x00, true, false, false),
+            (0x40, true, false, true),
+            (0x80, true, true, false),
+            (0xC0, true, true, true),
+            (0xC1, false, true, true),
+        ];
+
+        for &(byte, zero, negative, overflow) in cases.iter() {
+            // BIT $10
+            let (mut cpu, mut bus) = setup(&[0x24, 0x10]);
+            bus.write(0x0010, byte);
+            cpu.a = 0x0F;
+
+            assert_eq!(cpu.step(&mut bus), 3);
+            assert_eq!(cpu.get_flag(Flags::Zero), zero, "{:02X}", byte);
+            assert_eq!(cpu.get_flag(Flags::Negative), negative, "{:02X}", byte);
+            assert_eq!(cpu.get_flag(Flags::Overflow), overflow, "{:02X}", byte);
+            assert_eq!(cpu.a, 0x0F);
+        }
+    }
+
+    #[test]
+    fn stack_round_trip() {
+        let (mut cpu, mut bus) = setup(&[]);
+
+        cpu.stack_push(0x11, &mut bus);
+        cpu.stack_push_word(0xBEEF, &mut bus);
+        assert_eq!(cpu.sp, 0xFA);
+        assert_eq!(bus.read(0x01FD), 0x11);
+        assert_eq!(bus.read(0x01FC), 0xBE);
+        assert_eq!(bus.read(0x01FB), 0xEF);
+
+        assert_eq!(cpu.stack_pop_word(&mut bus), 0xBEEF);
+        assert_eq!(cpu.stack_pop(&mut bus), 0x11);
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn stack_pop_wraps_within_page_one() {
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0x0100, 0x33);
+        cpu.sp = 0xFF;
+
+        assert_eq!(cpu.stack_pop(&mut bus), 0x33);
+        assert_eq!(cpu.sp, 0x00);
+
+        // A word popped across the wrap
+        bus.write(0x01FF, 0x34);
+        bus.write(0x0100, 0x12);
+        cpu.sp = 0xFE;
+        assert_eq!(cpu.stack_pop_word(&mut bus), 0x1234);
+    }
+
+    #[test]
+    fn stack_never_overflows() {
+        let (mut cpu, mut bus) = setup(&[]);
+
+        // More pushes than the page holds just keep wrapping
+        for i in 0..0x300 {
+            cpu.stack_push(i as u8, &mut bus);
+        }
+        assert_eq!(cpu.sp, 0xFD);
+
+        for i in (0x200..0x300).rev() {
+            assert_eq!(cpu.stack_pop(&mut bus), i as u8);
+        }
+        assert_eq!(cpu.sp, 0xFD);
+    }
+
+    #[test]
+    fn jmp_indirect_does_not_cross_pages() {
+        // JMP ($02FF), the high byte comes from $0200, not $0300
+        let (mut cpu, mut bus) = setup(&[0x6C, 0xFF, 0x02]);
+        bus.write(0x02FF, 0x00);
+        bus.write(0x0200, 0x80);
+        bus.write(0x0300, 0x90);
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.pc, 0x8000);
+    }
+
+    #[test]
+    fn lda_indirect_y_page_cross_timing() {
+        // LDA ($20),Y / LDA ($20),Y with the pointer at $04F0
+        let (mut cpu, mut bus) = setup(&[0xB1, 0x20, 0xB1, 0x20]);
+        bus.write(0x0020, 0xF0);
+        bus.write(0x0021, 0x04);
+        bus.write(0x04FF, 0x11);
+        bus.write(0x0500, 0x22);
+
+        cpu.y = 0x0F;
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.a, 0x11);
+
+        cpu.y = 0x10;
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.a, 0x22);
+    }
+
+    #[test]
+    fn indexed_indirect_pointer_wraps_in_zero_page() {
+        // LDA ($FE,X) with X = 1, the pointer is split across $FF/$00
+        let (mut cpu, mut bus) = setup(&[0xA1, 0xFE]);
+        bus.write(0x00FF, 0x34);
+        bus.write(0x0000, 0x02);
+        bus.write(0x0100, 0x05);
+        bus.write(0x0234, 0x99);
+        cpu.x = 0x01;
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.a, 0x99);
+    }
+
+    #[test]
+    fn indirect_indexed_pointer_wraps_in_zero_page() {
+        // LDA ($FF),Y with Y = $34, the pointer is split across $FF/$00
+        let (mut cpu, mut bus) = setup(&[0xB1, 0xFF]);
+        bus.write(0x00FF, 0x00);
+        bus.write(0x0000, 0x03);
+        bus.write(0x0100, 0x05);
+        bus.write(0x0334, 0x89);
+        cpu.y = 0x34;
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(cpu.a, 0x89);
+    }
+
+    #[test]
+    fn branch_wraps_around_the_address_space() {
+        // BNE -4 at $0000 lands on $FFFE
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0x0000, 0xD0);
+        bus.write(0x0001, 0xFC);
+        cpu.pc = 0x0000;
+
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.pc, 0xFFFE);
+
+        // BNE +4 at $FFFC lands on $0002
+        bus.write(0xFFFC, 0xD0);
+        bus.write(0xFFFD, 0x04);
+        cpu.pc = 0xFFFC;
+
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.pc, 0x0002);
+    }
+
+    #[test]
+    fn branch_timing_by_direction() {
+        // (address of BNE, offset, taken, cycles, PC after)
+        let cases = [
+            (0x0700, 0x10, true, 3, 0x0712),
+            (0x07F0, 0x20, true, 4, 0x0812),
+            (0x0710, 0xF8, true, 3, 0x070A),
+            (0x0700, 0xF0, true, 4, 0x06F2),
+            (0x0700, 0x10, false, 2, 0x0702),
+            (0x07F0, 0x20, false, 2, 0x07F2),
+            (0x0710, 0xF8, false, 2, 0x0712),
+            (0x0700, 0xF0, false, 2, 0x0702),
+        ];
+
+        for &(addr, offset, taken, cycles, target) in cases.iter() {
+            let (mut cpu, mut bus) = setup(&[]);
+            bus.write(addr, 0xD0);
+            bus.write(addr + 1, offset);
+            cpu.pc = addr;
+            cpu.set_flag(Flags::Zero, !taken);
+
+            assert_eq!(cpu.step(&mut bus), cycles, "{:04X} {:02X} {}", addr, offset, taken);
+            assert_eq!(cpu.pc, target, "{:04X} {:02X} {}", addr, offset, taken);
+        }
+    }
+
+    // Cartridge space RAM that raises its IRQ line once an address is read
+    struct IrqOnRead {
+        ram: FlatRam,
+        addr: u16,
+        irq: bool,
+    }
+
+    impl Mapper for IrqOnRead {
+        fn cpu_read(&mut self, addr: u16) -> u8 {
+            self.irq |= addr == self.addr;
+            self.ram.cpu_read(addr)
+        }
+
+        fn cpu_write(&mut self, addr: u16, val: u8) {
+            self.ram.cpu_write(addr, val);
+        }
+
+        fn ppu_read(&mut self, addr: u16) -> u8 {
+            self.ram.ppu_read(addr)
+        }
+
+        fn ppu_write(&mut self, addr: u16, val: u8) {
+            self.ram.ppu_write(addr, val);
+        }
+
+        fn mirroring(&self) -> Mirroring {
+            Mirroring::Horizontal
+        }
+
+        fn irq(&self) -> bool {
+            self.irq
+        }
+
+        fn cartridge(&self) -> Option<&Cartridge> {
+            None
+        }
+
+        fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+            None
+        }
+    }
+
+    // Load a program at the given address of an IrqOnRead mapper,
+    // with the IRQ handler at $9000
+    fn setup_irq_on_read(addr: u16, program: &[u8], trigger: u16) -> (MOS6502, Bus) {
+        let mut bus = Bus::new();
+        bus.set_mapper(Box::new(IrqOnRead { ram: FlatRam::new(), addr: trigger, irq: false }));
+
+        for (i, byte) in program.iter().enumerate() {
+            bus.write(addr + i as u16, *byte);
+        }
+        bus.write(0xFFFE, 0x00);
+        bus.write(0xFFFF, 0x90);
+
+        let mut cpu = MOS6502::new();
+        cpu.pc = addr;
+
+        (cpu, bus)
+    }
+
+    #[test]
+    fn irq_raised_mid_instruction_waits_for_it_to_finish() {
+        // LDA $0200, the IRQ is raised by the operand fetch
+        let (mut cpu, mut bus) = setup_irq_on_read(0x8000, &[0xAD, 0x00, 0x02, 0xEA], 0x8001);
+
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.pc, 0x8003);
+
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(bus.read(0x01FD), 0x80);
+        assert_eq!(bus.read(0x01FC), 0x03);
+    }
+
+    #[test]
+    fn taken_branch_on_the_same_page_delays_irq() {
+        // BNE +0 / NOP / NOP, the IRQ is raised by the operand fetch
+        let program = [0xD0, 0x00, 0xEA, 0xEA];
+
+        // Taken and staying on the page: the branch polled too early
+        let (mut cpu, mut bus) = setup_irq_on_read(0x8000, &program, 0x8001);
+        assert_eq!(cpu.step(&mut bus), 3);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x8003);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x01FC), 0x03);
+
+        // Not taken: serviced right after the branch
+        let (mut cpu, mut bus) = setup_irq_on_read(0x8000, &program, 0x8001);
+        cpu.s = Flags::Zero as u8;
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x01FC), 0x02);
+
+        // Taken across a page: serviced right after the branch
+        let (mut cpu, mut bus) = setup_irq_on_read(0x80F0, &[0xD0, 0x20], 0x80F1);
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(bus.read(0x01FC), 0x12);
+    }
+
+    #[test]
+    fn pc_wraps_at_the_top_of_memory() {
+        // LDA $0480 at $FFFE, its high operand byte is at $0000
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0xFFFE, 0xAD);
+        bus.write(0xFFFF, 0x80);
+        bus.write(0x0000, 0x04);
+        bus.write(0x0480, 0x5A);
+        cpu.pc = 0xFFFE;
+
+        assert_eq!(cpu.step(&mut bus), 4);
+        assert_eq!(cpu.a, 0x5A);
+        assert_eq!(cpu.pc, 0x0001);
+
+        // NOP at $FFFF
+        bus.write(0xFFFF, 0xEA);
+        cpu.pc = 0xFFFF;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.pc, 0x0000);
+    }
+
+    #[test]
+    fn accumulator_mode_does_not_leak() {
+        // LSR A / ASL $10
+        let (mut cpu, mut bus) = setup(&[0x4A, 0x06, 0x10]);
+        bus.write(0x0010, 0x41);
+        cpu.a = 0x04;
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.a, 0x02);
+
+        assert_eq!(cpu.step(&mut bus), 5);
+        assert_eq!(bus.read(0x0010), 0x82);
+        assert_eq!(cpu.a, 0x02);
+    }
+
+    #[test]
+    fn add_with_carry_matches_a_reference_model() {
+        let mut cpu = MOS6502::new();
+
+        for a in 0..=0xFF {
+            for byte in 0..=0xFF {
+                for carry in 0..=1 {
+                    let sum = a + byte + carry;
+                    let signed = a as u8 as i8 as i16 + byte as u8 as i8 as i16 + carry as i16;
+                    let res = sum as u8;
+
+                    cpu.a = a as u8;
+                    cpu.s = 0;
+                    cpu.set_flag(Flags::Carry, carry == 1);
+                    cpu.add_with_carry(byte as u8);
+
+                    let case = format!("{:02X} + {:02X} + {}", a, byte, carry);
+                    assert_eq!(cpu.a, res, "{}", case);
+                    assert_eq!(cpu.get_flag(Flags::Carry), sum > 0xFF, "{}", case);
+                    assert_eq!(cpu.get_flag(Flags::Zero), res == 0, "{}", case);
+                    assert_eq!(cpu.get_flag(Flags::Overflow), !(-128..=127).contains(&signed), "{}", case);
+                    assert_eq!(cpu.get_flag(Flags::Negative), res & 0x80 != 0, "{}", case);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_branch_timing() {
+        // (opcode, flag tested, branch when the flag is set)
+        let branches = [
+            (0x10, Flags::Negative as u8, false),
+            (0x30, Flags::Negative as u8, true),
+            (0x50, Flags::Overflow as u8, false),
+            (0x70, Flags::Overflow as u8, true),
+            (0x90, Flags::Carry as u8, false),
+            (0xB0, Flags::Carry as u8, true),
+            (0xD0, Flags::Zero as u8, false),
+            (0xF0, Flags::Zero as u8, true),
+        ];
+        // (address of the branch, taken, cycles, PC after) with offset +$20
+        let cases = [
+            (0x0700, true, 3, 0x0722),
+            (0x07F0, true, 4, 0x0812),
+            (0x0700, false, 2, 0x0702),
+            (0x07F0, false, 2, 0x07F2),
+        ];
+
+        for &(opcode, flag, when_set) in branches.iter() {
+            for &(addr, taken, cycles, target) in cases.iter() {
+                let (mut cpu, mut bus) = setup(&[]);
+                bus.write(addr, opcode);
+                bus.write(addr + 1, 0x20);
+                cpu.pc = addr;
+                cpu.s = if taken == when_set { flag } else { 0 };
+
+                assert_eq!(cpu.step(&mut bus), cycles, "{:02X} at {:04X} {}", opcode, addr, taken);
+                assert_eq!(cpu.pc, target, "{:02X} at {:04X} {}", opcode, addr, taken);
+            }
+        }
+    }
+
+    #[test]
+    fn power_on_state() {
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0xFFFC, 0x00);
+        bus.write(0xFFFD, 0xC0);
+        cpu.a = 0x12;
+        cpu.x = 0x34;
+        cpu.y = 0x56;
+        cpu.sp = 0x78;
+        cpu.s = 0xFF;
+
+        cpu.power_on(&mut bus);
+        assert_eq!((cpu.a, cpu.x, cpu.y), (0x00, 0x00, 0x00));
+        assert_eq!(cpu.s, 0x24);
+        assert_eq!(cpu.sp, 0xFD);
+        assert_eq!(cpu.pc, 0xC000);
+        assert_eq!(cpu.cycles(), 7);
+    }
+
+    #[test]
+    fn reset_keeps_registers_and_ram() {
+        let (mut cpu, mut bus) = setup(&[]);
+        bus.write(0xFFFC, 0x00);
+        bus.write(0xFFFD, 0xC0);
+        cpu.power_on(&mut bus);
+
+        cpu.a = 0x12;
+        cpu.x = 0x34;
+        cpu.y = 0x56;
+        cpu.sp = 0xF0;
+        cpu.s = Flags::Carry as u8 | Flags::Unused as u8;
+        bus.write(0x0010, 0x55);
+        bus.write(0x01F0, 0x66);
+
+        cpu.reset(&mut bus);
+        assert_eq!((cpu.a, cpu.x, cpu.y), (0x12, 0x34, 0x56));
+        assert_eq!(cpu.s, 0x25);
+        assert_eq!(cpu.sp, 0xED);
+        assert_eq!(cpu.pc, 0xC000);
+        assert_eq!(cpu.cycles(), 14);
+
+        // Nothing is written to the stack
+        assert_eq!(bus.read(0x0010), 0x55);
+        assert_eq!(bus.read(0x01F0), 0x66);
+        assert_eq!(bus.read(0x01EF), 0x00);
+    }
+
+    fn setup_nmos(program: &[u8]) -> (MOS6502, Bus) {
+        let (_, bus) = setup(program);
+        let mut cpu = MOS6502::new_nmos();
+        cpu.pc = 0x0600;
+
+        (cpu, bus)
+    }
+
+    #[test]
+    fn decimal_mode_results() {
+        // (opcode, A, operand, carry in, A out, carry out, Z, N, V)
+        let cases = [
+            (0x69, 0x09, 0x01, false, 0x10, false, false, false, false),
+            (0x69, 0x12, 0x34, false, 0x46, false, false, false, false),
+            (0x69, 0x58, 0x46, true, 0x05, true, false, true, true),
+            (0x69, 0x81, 0x92, false, 0x73, true, false, false, true),
+            // Z comes from the binary sum $9A
+            (0x69, 0x99, 0x01, false, 0x00, true, false, true, false),
+            // N and V come from the sum with only the low digit adjusted
+            (0x69, 0x79, 0x00, true, 0x80, false, false, true, true),
+            (0xE9, 0x46, 0x12, true, 0x34, true, false, false, false),
+            (0xE9, 0x40, 0x13, true, 0x27, true, false, false, false),
+            (0xE9, 0x32, 0x02, false, 0x29, true, false, false, false),
+            (0xE9, 0x12, 0x21, true, 0x91, false, false, true, false),
+            (0xE9, 0x21, 0x34, true, 0x87, false, false, true, false),
+        ];
+
+        for &(opcode, a, byte, carry, res, carry_out, zero, negative, overflow) in cases.iter() {
+            let (mut cpu, mut bus) = setup_nmos(&[opcode, byte]);
+            cpu.a = a;
+            cpu.s = Flags::Decimal as u8 | if carry { Flags::Carry as u8 } else { 0 };
+
+            let case = format!("{:02X} {:02X} {:02X} {}", opcode, a, byte, carry);
+            assert_eq!(cpu.step(&mut bus), 2, "{}", case);
+            assert_eq!(cpu.a, res, "{}", case);
+            assert_eq!(cpu.get_flag(Flags::Carry), carry_out, "{}", case);
+            assert_eq!(cpu.get_flag(Flags::Zero), zero, "{}", case);
+            assert_eq!(cpu.get_flag(Flags::Negative), negative, "{}", case);
+            assert_eq!(cpu.get_flag(Flags::Overflow), overflow, "{}", case);
+        }
+    }
+
+    #[test]
+    fn decimal_flag_is_ignored_by_default() {
+        // ADC #$01 / SBC #$01
+        let (mut cpu, mut bus) = setup(&[0x69, 0x01, 0xE9, 0x01]);
+        cpu.a = 0x09;
+        cpu.s = Flags::Decimal as u8;
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x0A);
+
+        cpu.set_flag(Flags::Carry, true);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x09);
+
+        // NMOS BCD still needs the Decimal flag
+        let (mut cpu, mut bus) = setup_nmos(&[0x69, 0x01]);
+        cpu.a = 0x09;
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.a, 0x0A);
+    }
+
+    #[test]
+    fn nmi_pushes_state_and_rti_returns() {
+        // NOP / NOP, the handler at $9000 is RTI
+        let (mut cpu, mut bus) = setup(&[0xEA, 0xEA]);
+        bus.write(0xFFFA, 0x00);
+        bus.write(0xFFFB, 0x90);
+        bus.write(0x9000, 0x40);
+        cpu.step(&mut bus);
+
+        // Taken with Interrupt Disable set
+        cpu.s = Flags::Interrupt as u8 | Flags::Carry as u8;
+        let cycles = cpu.cycles();
+        cpu.nmi(&mut bus);
+
+        assert_eq!(cpu.cycles() - cycles, 7);
+        assert_eq!(cpu.pc, 0x9000);
+        assert_eq!(cpu.sp, 0xFA);
+        assert_eq!(bus.read(0x01FD), 0x06);
+        assert_eq!(bus.read(0x01FC), 0x01);
+        // Break clear, Unused set
+        assert_eq!(bus.read(0x01FB), 0x25);
+
+        assert_eq!(cpu.step(&mut bus), 6);
+        assert_eq!(cpu.pc, 0x0601);
+        assert_eq!(cpu.sp, 0xFD);
+        assert_eq!(cpu.s, 0x25);
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.pc, 0x0602);
+    }
+
+    #[test]
+    fn nmi_line_held_does_not_retrigger() {
+        let (mut cpu, mut bus) = setup(&[0xEA; 4]);
+        bus.write(0xFFFA, 0x00);
+        bus.write(0xFFFB, 0x90);
+        for i in 0..16 {
+            bus.write(0x9000 + i, 0xEA);
+        }
+
+        // Let the PPU pull the line at the start of vblank
+        bus.write(0x2000, 0x80);
+        while !bus.nmi() {
+            bus.run_ppu(1);
+        }
+
+        assert_eq!(cpu.step(&mut bus), 2);
+        assert_eq!(cpu.step(&mut bus), 7);
+        assert_eq!(cpu.pc, 0x9000);
+
+        for _ in 0..16 {
+            assert_eq!(cpu.step(&mut bus), 2);
+            assert!(bus.nmi());
         }
     }
 }
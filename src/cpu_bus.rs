@@ -1,26 +1,225 @@
-use crate::cpu_6502;
-use cpu_6502::MOS6502;
+use crate::cartridge::Cartridge;
+use crate::mapper::{FlatRam, Mapper};
+use crate::ppu::Ppu;
+
+// Memory interface of the CPU.
+// Implemented by the NES bus, and by mock buses in tests.
+pub trait CpuBus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, byte: u8);
+
+    // Level of the IRQ line driven by the devices on the bus
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Level of the NMI line, driven by the PPU
+    fn nmi(&self) -> bool {
+        false
+    }
+}
 
 pub struct Bus {
-    cpu: MOS6502,
-    ram: [u8; 0xFFFF]
+    wram: [u8; 0x0800], // 2KB of internal work RAM
+    ppu: Ppu,
+    io: [u8; 0x20],     // APU and I/O registers (not emulated yet)
+    mapper: Box<dyn Mapper>,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Bus {
     pub fn new() -> Self {
-        let out = Self {
-            cpu: MOS6502::new(),
-            ram: [0; 0xFFFF]
-        };
-        
-        out
+        Self {
+            wram: [0; 0x0800],
+            ppu: Ppu::new(),
+            io: [0; 0x20],
+            mapper: Box::new(FlatRam::new()),
+        }
+    }
+
+    // The inserted cartridge, e.g. to persist its battery backed RAM
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.mapper.cartridge()
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.mapper.cartridge_mut()
+    }
+
+    // Run the PPU alongside the given number of CPU cycles.
+    // The PPU draws 3 dots per CPU cycle.
+    pub fn run_ppu(&mut self, cycles: u8) {
+        for _ in 0..cycles as u16 * 3 {
+            self.ppu.tick(&mut *self.mapper);
+        }
     }
 
-    pub fn read(&mut self, addr: u16) -> u8 {
-        self.ram[addr as usize]
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    // Plug in the mapper of the inserted cartridge
+    pub fn set_mapper(&mut self, mapper: Box<dyn Mapper>) {
+        self.mapper = mapper;
+    }
+}
+
+impl CpuBus for Bus {
+    fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            // Work RAM is mirrored every 2KB up to $1FFF
+            0x0000..=0x1FFF => self.wram[(addr & 0x07FF) as usize],
+            // PPU registers are mirrored every 8 bytes up to $3FFF
+            0x2000..=0x3FFF => self.ppu.read_register(&mut *self.mapper, addr),
+            0x4000..=0x401F => self.io[(addr & 0x001F) as usize],
+            _ => self.mapper.cpu_read(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.wram[(addr & 0x07FF) as usize] = byte,
+            0x2000..=0x3FFF => self.ppu.write_register(&mut *self.mapper, addr, byte),
+            0x4000..=0x401F => self.io[(addr & 0x001F) as usize] = byte,
+            _ => self.mapper.cpu_write(addr, byte),
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.mapper.irq()
+    }
+
+    fn nmi(&self) -> bool {
+        self.ppu.nmi()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Mirroring;
+
+    #[test]
+    fn top_of_memory_is_reachable() {
+        let mut bus = Bus::new();
+
+        bus.write(0xFFFF, 0xA5);
+        assert_eq!(bus.read(0xFFFF), 0xA5);
     }
 
-    pub fn write(&mut self, addr: u16, byte: u8) {
-        self.ram[addr as usize] = byte;
+    // A register that counts how many times it has been read
+    struct Counter {
+        reads: u8,
+    }
+
+    impl CpuBus for Counter {
+        fn read(&mut self, _addr: u16) -> u8 {
+            self.reads += 1;
+            self.reads
+        }
+
+        fn write(&mut self, _addr: u16, _byte: u8) {}
+    }
+
+    #[test]
+    fn reads_can_mutate_registers() {
+        let bus: &mut dyn CpuBus = &mut Counter { reads: 0 };
+
+        assert_eq!(bus.read(0x2002), 1);
+        assert_eq!(bus.read(0x2002), 2);
+        assert_eq!(bus.read(0x4016), 3);
+    }
+
+    #[test]
+    fn work_ram_is_mirrored() {
+        let mut bus = Bus::new();
+
+        bus.write(0x0001, 0x5A);
+        assert_eq!(bus.read(0x0801), 0x5A);
+        assert_eq!(bus.read(0x1001), 0x5A);
+        assert_eq!(bus.read(0x1801), 0x5A);
+
+        bus.write(0x1FFF, 0xC3);
+        assert_eq!(bus.read(0x07FF), 0xC3);
+    }
+
+    #[test]
+    fn ppu_registers_are_mirrored() {
+        let mut bus = Bus::new();
+
+        // PPUCTRL is write only, reading its mirror returns the bus latch
+        bus.write(0x2000, 0x5A);
+        assert_eq!(bus.read(0x3FF8), 0x5A);
+
+        // OAMADDR and OAMDATA through the last mirror. Each OAMDATA
+        // write moves OAMADDR on, so the bytes land in a row
+        bus.write(0x3FFB, 0x10);
+        bus.write(0x3FFC, 0x77);
+        bus.write(0x3FFC, 0x88);
+
+        // Read back through other mirrors
+        bus.write(0x2A03, 0x10);
+        assert_eq!(bus.read(0x2C04), 0x77);
+        bus.write(0x3003, 0x11);
+        assert_eq!(bus.read(0x3804), 0x88);
+
+        // PPUADDR and PPUDATA through the last mirror
+        bus.write(0x3FFE, 0x23);
+        bus.write(0x3FFE, 0x00);
+        bus.write(0x3FFF, 0x99);
+
+        // Read back through other mirrors, past the read buffer
+        bus.write(0x2806, 0x23);
+        bus.write(0x2806, 0x00);
+        bus.read(0x3007);
+        assert_eq!(bus.read(0x2C0F), 0x99);
+    }
+
+    // PRG-ROM that returns the high byte of the address
+    struct Stub;
+
+    impl Mapper for Stub {
+        fn cpu_read(&mut self, addr: u16) -> u8 {
+            (addr >> 8) as u8
+        }
+
+        fn cpu_write(&mut self, _addr: u16, _val: u8) {}
+
+        fn ppu_read(&mut self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn ppu_write(&mut self, _addr: u16, _val: u8) {}
+
+        fn mirroring(&self) -> Mirroring {
+            Mirroring::Horizontal
+        }
+
+        fn cartridge(&self) -> Option<&Cartridge> {
+            None
+        }
+
+        fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+            None
+        }
+    }
+
+    #[test]
+    fn cartridge_space_goes_through_the_mapper() {
+        let mut bus = Bus::new();
+        bus.set_mapper(Box::new(Stub));
+
+        assert_eq!(bus.read(0x4020), 0x40);
+        assert_eq!(bus.read(0x8000), 0x80);
+        assert_eq!(bus.read(0xFFFC), 0xFF);
+
+        // Below $4020 the mapper is not involved
+        bus.write(0x401F, 0x12);
+        assert_eq!(bus.read(0x401F), 0x12);
     }
 }
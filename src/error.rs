@@ -0,0 +1,37 @@
+use std::fmt;
+
+// Exceptional conditions surfaced by the emulator.
+// Things that can happen on real hardware (like the stack wrapping
+// around) are emulated and never reported as errors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EmuError {
+    Jammed(u16), // The CPU halted, holds the address it halted at
+    UnstableOpcode { opcode: u8, addr: u16 }, // Unstable opcode met in the Error mode
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::Jammed(pc) => write!(f, "CPU jammed at ${:04X}", pc),
+            EmuError::UnstableOpcode { opcode, addr } => {
+                write!(f, "unstable opcode ${:02X} at ${:04X}", opcode, addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmuError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(EmuError::Jammed(0x8002).to_string(), "CPU jammed at $8002");
+        assert_eq!(
+            EmuError::UnstableOpcode { opcode: 0x8B, addr: 0xC0DE }.to_string(),
+            "unstable opcode $8B at $C0DE"
+        );
+    }
+}
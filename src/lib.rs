@@ -0,0 +1,6 @@
+pub mod cartridge;
+pub mod cpu_6502;
+pub mod cpu_bus;
+pub mod error;
+pub mod mapper;
+pub mod ppu;
@@ -1,6 +1,3 @@
-mod cpu_6502;
-mod cpu_bus;
-
 fn main() {
     unimplemented!();
 }
@@ -0,0 +1,138 @@
+use crate::cartridge::{CartError, Cartridge, Mirroring};
+
+mod cnrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+
+pub use cnrom::Cnrom;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+
+// Cartridge hardware as seen from the buses.
+// The mapper decides which bank of PRG/CHR memory an address lands in.
+pub trait Mapper {
+    // Read from the cartridge space of the CPU bus ($4020-$FFFF)
+    fn cpu_read(&mut self, addr: u16) -> u8;
+
+    // Write to the cartridge space of the CPU bus ($4020-$FFFF)
+    fn cpu_write(&mut self, addr: u16, val: u8);
+
+    // Read from the pattern tables on the PPU bus ($0000-$1FFF)
+    fn ppu_read(&mut self, addr: u16) -> u8;
+
+    // Write to the pattern tables on the PPU bus ($0000-$1FFF)
+    fn ppu_write(&mut self, addr: u16, val: u8);
+
+    // Current nametable arrangement, some mappers can switch it
+    fn mirroring(&self) -> Mirroring;
+
+    // Level of the IRQ line driven by the cartridge
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // The inserted cartridge, None when there is none
+    fn cartridge(&self) -> Option<&Cartridge>;
+
+    fn cartridge_mut(&mut self) -> Option<&mut Cartridge>;
+}
+
+// Build the mapper named by the cartridge header
+pub fn from_cartridge(cart: Cartridge) -> Result<Box<dyn Mapper>, CartError> {
+    match cart.mapper {
+        0 => Ok(Box::new(Nrom::new(cart))),
+        1 => Ok(Box::new(Mmc1::new(cart))),
+        3 => Ok(Box::new(Cnrom::new(cart))),
+        4 => Ok(Box::new(Mmc3::new(cart))),
+        id => Err(CartError::UnsupportedMapper(id)),
+    }
+}
+
+// Stand-in used while no cartridge is inserted.
+// The whole cartridge space behaves like plain RAM.
+pub struct FlatRam {
+    mem: Vec<u8>,
+    chr: Vec<u8>,
+}
+
+impl Default for FlatRam {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatRam {
+    pub fn new() -> Self {
+        Self {
+            mem: vec![0; 0x10000],
+            chr: vec![0; 0x2000],
+        }
+    }
+}
+
+impl Mapper for FlatRam {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.mem[addr as usize]
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        self.mem[addr as usize] = val;
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr & 0x1FFF) as usize]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.chr[(addr & 0x1FFF) as usize] = val;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn cartridge(&self) -> Option<&Cartridge> {
+        None
+    }
+
+    fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Mirroring;
+
+    // A cartridge for mapper tests. Every 8KB of PRG ROM is filled with
+    // its 8KB bank number, every 1KB of CHR ROM with its 1KB bank number.
+    pub fn cart(mapper: u8, prg_size: usize, chr_size: usize) -> Cartridge {
+        Cartridge {
+            mapper,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            prg_rom: (0..prg_size).map(|i| (i / 0x2000) as u8).collect(),
+            prg_ram: vec![0; 0x2000],
+            chr: if chr_size == 0 {
+                vec![0; 0x2000]
+            } else {
+                (0..chr_size).map(|i| (i / 0x0400) as u8).collect()
+            },
+            chr_ram: chr_size == 0,
+            vram: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn picks_the_mapper_from_the_header() {
+        assert!(from_cartridge(cart(0, 0x4000, 0x2000)).is_ok());
+        assert!(from_cartridge(cart(4, 0x8000, 0x2000)).is_ok());
+        assert_eq!(
+            from_cartridge(cart(7, 0x8000, 0x2000)).err(),
+            Some(CartError::UnsupportedMapper(7))
+        );
+    }
+}
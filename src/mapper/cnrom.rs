@@ -0,0 +1,80 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use super::Mapper;
+
+// CNROM (Mapper 3)
+// PRG ROM is fixed like NROM. Any write to $8000-$FFFF
+// selects the 8KB CHR ROM bank seen by the PPU.
+pub struct Cnrom {
+    cart: Cartridge,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(cart: Cartridge) -> Self {
+        Self {
+            cart,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0xFFFF => {
+                let offset = (addr - 0x8000) as usize % self.cart.prg_rom.len();
+                self.cart.prg_rom[offset]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            self.chr_bank = val & 0x03;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let offset = self.chr_bank as usize * 0x2000 + (addr & 0x1FFF) as usize;
+        self.cart.chr[offset % self.cart.chr.len()]
+    }
+
+    // CHR ROM can't be written
+    fn ppu_write(&mut self, _addr: u16, _val: u8) {}
+
+    fn mirroring(&self) -> Mirroring {
+        self.cart.mirroring
+    }
+
+    fn cartridge(&self) -> Option<&Cartridge> {
+        Some(&self.cart)
+    }
+
+    fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        Some(&mut self.cart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::cart;
+
+    #[test]
+    fn writes_select_the_chr_bank() {
+        let mut cnrom = Cnrom::new(cart(3, 0x8000, 0x8000));
+        assert_eq!(cnrom.ppu_read(0x0000), 0);
+
+        cnrom.cpu_write(0x8000, 0x02);
+        assert_eq!(cnrom.ppu_read(0x0000), 16);
+        assert_eq!(cnrom.ppu_read(0x1FFF), 23);
+
+        // PRG ROM stays put
+        assert_eq!(cnrom.cpu_read(0x8000), 0);
+        assert_eq!(cnrom.cpu_read(0xFFFF), 3);
+
+        cnrom.cpu_write(0xFFFF, 0x03);
+        assert_eq!(cnrom.ppu_read(0x0400), 25);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use super::Mapper;
+
+// MMC1 (Mapper 1)
+// The registers are loaded serially. Each write to $8000-$FFFF shifts
+// bit 0 into a 5-bit shift register, and the fifth write copies it into
+// the register selected by bits 13-14 of that write's address:
+// $8000-$9FFF: Control  - ...CPPMM
+//              MM: Mirroring (0: single lower, 1: single upper, 2: vertical, 3: horizontal)
+//              PP: PRG banking (0, 1: 32KB, 2: first bank fixed at $8000, 3: last bank fixed at $C000)
+//              C:  CHR banking (0: one 8KB bank, 1: two 4KB banks)
+// $A000-$BFFF: CHR bank 0
+// $C000-$DFFF: CHR bank 1
+// $E000-$FFFF: PRG bank   - ...RPPPP (R: PRG RAM disable)
+// A write with bit 7 set resets the shift register and locks
+// the last PRG bank at $C000.
+pub struct Mmc1 {
+    cart: Cartridge,
+    shift: u8, // Shift register, the 1 marks when it is full
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+}
+
+impl Mmc1 {
+    pub fn new(cart: Cartridge) -> Self {
+        Self {
+            cart,
+            shift: 0x10,
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+        }
+    }
+
+    // Load a full shift register into the register picked by the address
+    fn load_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = val,
+            0xA000..=0xBFFF => self.chr_bank_0 = val,
+            0xC000..=0xDFFF => self.chr_bank_1 = val,
+            _ => self.prg_bank = val,
+        }
+    }
+
+    // Offset into PRG ROM of a CPU address in $8000-$FFFF
+    fn prg_offset(&self, addr: u16) -> usize {
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let last = self.cart.prg_rom.len() / 0x4000 - 1;
+
+        let bank = match (self.control >> 2) & 0x03 {
+            // 32KB mode ignores the low bit of the bank number
+            0 | 1 => (bank & !1) + ((addr as usize >> 14) & 1),
+            2 => if addr < 0xC000 { 0 } else { bank },
+            _ => if addr < 0xC000 { bank } else { last },
+        };
+
+        (bank * 0x4000 + (addr & 0x3FFF) as usize) % self.cart.prg_rom.len()
+    }
+
+    // Offset into CHR memory of a PPU address in $0000-$1FFF
+    fn chr_offset(&self, addr: u16) -> usize {
+        let offset = if self.control & 0x10 == 0 {
+            // 8KB mode ignores the low bit of the bank number
+            (self.chr_bank_0 & !1) as usize * 0x1000 + (addr & 0x1FFF) as usize
+        } else {
+            let bank = if addr < 0x1000 { self.chr_bank_0 } else { self.chr_bank_1 };
+            bank as usize * 0x1000 + (addr & 0x0FFF) as usize
+        };
+
+        offset % self.cart.chr.len()
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            // Bit 4 of the PRG bank register disables the RAM
+            0x6000..=0x7FFF if self.prg_bank & 0x10 == 0 => self.cart.prg_ram[(addr & 0x1FFF) as usize],
+            0x8000..=0xFFFF => self.cart.prg_rom[self.prg_offset(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            // Bit 4 of the PRG bank register disables the RAM
+            0x6000..=0x7FFF if self.prg_bank & 0x10 == 0 => {
+                self.cart.prg_ram[(addr & 0x1FFF) as usize] = val;
+            }
+            0x8000..=0xFFFF => {
+                if val & 0x80 != 0 {
+                    self.shift = 0x10;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                let full = self.shift & 0x01 != 0;
+                self.shift = (self.shift >> 1) | ((val & 0x01) << 4);
+
+                if full {
+                    self.load_register(addr, self.shift);
+                    self.shift = 0x10;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.cart.chr[self.chr_offset(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.cart.chr_ram {
+            let offset = self.chr_offset(addr);
+            self.cart.chr[offset] = val;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn cartridge(&self) -> Option<&Cartridge> {
+        Some(&self.cart)
+    }
+
+    fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        Some(&mut self.cart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::cart;
+
+    // Load a register through the five serial writes, low bit first
+    fn load(mmc1: &mut Mmc1, addr: u16, val: u8) {
+        for i in 0..5 {
+            mmc1.cpu_write(addr, (val >> i) & 0x01);
+        }
+    }
+
+    #[test]
+    fn registers_load_on_the_fifth_write() {
+        let mut mmc1 = Mmc1::new(cart(1, 0x20000, 0x2000));
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 14);
+
+        // Four writes leave the bank alone
+        for &bit in [1, 1, 0, 0].iter() {
+            mmc1.cpu_write(0xE000, bit);
+        }
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+
+        // The fifth picks 16KB bank 3 at $8000, the last stays at $C000
+        mmc1.cpu_write(0xE000, 0x00);
+        assert_eq!(mmc1.cpu_read(0x8000), 6);
+        assert_eq!(mmc1.cpu_read(0xC000), 14);
+
+        // Bit 7 resets the shift register part way through
+        mmc1.cpu_write(0xE000, 0x01);
+        mmc1.cpu_write(0xE000, 0x80);
+        load(&mut mmc1, 0xE000, 0x02);
+        assert_eq!(mmc1.cpu_read(0x8000), 4);
+    }
+
+    #[test]
+    fn control_picks_prg_and_chr_modes_and_mirroring() {
+        let mut mmc1 = Mmc1::new(cart(1, 0x20000, 0x8000));
+        load(&mut mmc1, 0xE000, 0x03);
+
+        // 32KB PRG, 8KB CHR, vertical
+        load(&mut mmc1, 0x8000, 0x02);
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+        assert_eq!(mmc1.cpu_read(0x8000), 4);
+        assert_eq!(mmc1.cpu_read(0xC000), 6);
+
+        load(&mut mmc1, 0xA000, 0x03);
+        assert_eq!(mmc1.ppu_read(0x0000), 8);
+        assert_eq!(mmc1.ppu_read(0x1000), 12);
+
+        // First bank fixed at $8000, two 4KB CHR banks, horizontal
+        load(&mut mmc1, 0x8000, 0x1B);
+        load(&mut mmc1, 0xC000, 0x05);
+        assert_eq!(mmc1.mirroring(), Mirroring::Horizontal);
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 6);
+        assert_eq!(mmc1.ppu_read(0x0000), 12);
+        assert_eq!(mmc1.ppu_read(0x1000), 20);
+    }
+
+    #[test]
+    fn prg_ram_can_be_disabled() {
+        let mut mmc1 = Mmc1::new(cart(1, 0x8000, 0x2000));
+        mmc1.cpu_write(0x6000, 0x42);
+        assert_eq!(mmc1.cpu_read(0x6000), 0x42);
+
+        load(&mut mmc1, 0xE000, 0x10);
+        mmc1.cpu_write(0x6000, 0x24);
+        assert_eq!(mmc1.cpu_read(0x6000), 0);
+
+        load(&mut mmc1, 0xE000, 0x00);
+        assert_eq!(mmc1.cpu_read(0x6000), 0x42);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use super::Mapper;
+
+// MMC3 (Mapper 4)
+// Registers are selected by the address range and whether it is even or odd:
+// $8000 even: Bank select - CP...RRR
+//             RRR: Bank register (R0-R7) updated by the next bank data write
+//             P:   PRG mode (0: $8000 swappable, $C000 fixed to the second last bank,
+//                            1: $C000 swappable, $8000 fixed to the second last bank)
+//             C:   CHR A12 inversion (swaps the 2KB and 1KB CHR halves)
+// $8001 odd:  Bank data
+// $A000 even: Mirroring (0: vertical, 1: horizontal)
+// $A001 odd:  PRG RAM protect - EW......
+// $C000 even: IRQ latch, reload value of the scanline counter
+// $C001 odd:  IRQ reload, reloads the counter on the next clock
+// $E000 even: IRQ disable, also acknowledges a pending IRQ
+// $E001 odd:  IRQ enable
+// The scanline counter is clocked by rising edges of PPU A12, which happen
+// once per scanline when the background and sprites use different
+// pattern tables. The IRQ fires when the counter reaches zero.
+pub struct Mmc3 {
+    cart: Cartridge,
+    bank_select: u8,
+    banks: [u8; 8], // R0-R7
+    mirroring: Mirroring,
+    four_screen: bool,
+    ram_enabled: bool,
+    ram_protected: bool,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq: bool,
+    a12: bool, // Last seen state of PPU A12
+}
+
+impl Mmc3 {
+    pub fn new(cart: Cartridge) -> Self {
+        Self {
+            bank_select: 0,
+            banks: [0; 8],
+            mirroring: cart.mirroring,
+            four_screen: cart.mirroring == Mirroring::FourScreen,
+            cart,
+            ram_enabled: true,
+            ram_protected: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq: false,
+            a12: false,
+        }
+    }
+
+    // Offset into PRG ROM of a CPU address in $8000-$FFFF
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = self.cart.prg_rom.len() / 0x2000;
+        let second_last = banks - 2;
+        let swap = self.bank_select & 0x40 != 0;
+
+        let bank = match (addr >> 13) & 0x03 {
+            0 => if swap { second_last } else { self.banks[6] as usize },
+            1 => self.banks[7] as usize,
+            2 => if swap { self.banks[6] as usize } else { second_last },
+            _ => banks - 1,
+        };
+
+        ((bank % banks) * 0x2000) | (addr & 0x1FFF) as usize
+    }
+
+    // Offset into CHR memory of a PPU address in $0000-$1FFF
+    fn chr_offset(&self, addr: u16) -> usize {
+        // A12 inversion swaps the two pattern table halves
+        let addr = if self.bank_select & 0x80 != 0 { addr ^ 0x1000 } else { addr };
+
+        let bank = match addr & 0x1FFF {
+            0x0000..=0x07FF => (self.banks[0] & !1) as usize + ((addr >> 10) & 1) as usize,
+            0x0800..=0x0FFF => (self.banks[1] & !1) as usize + ((addr >> 10) & 1) as usize,
+            0x1000..=0x13FF => self.banks[2] as usize,
+            0x1400..=0x17FF => self.banks[3] as usize,
+            0x1800..=0x1BFF => self.banks[4] as usize,
+            _ => self.banks[5] as usize,
+        };
+
+        (bank * 0x0400 + (addr & 0x03FF) as usize) % self.cart.chr.len()
+    }
+
+    // Clock the scanline counter
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq = true;
+        }
+    }
+
+    // Watch PPU A12 for the rising edges that clock the scanline counter
+    fn watch_a12(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+
+        if a12 && !self.a12 {
+            self.clock_irq_counter();
+        }
+
+        self.a12 = a12;
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled => self.cart.prg_ram[(addr & 0x1FFF) as usize],
+            0x8000..=0xFFFF => self.cart.prg_rom[self.prg_offset(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        let even = addr & 0x01 == 0;
+
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled && !self.ram_protected => {
+                self.cart.prg_ram[(addr & 0x1FFF) as usize] = val;
+            }
+            0x8000..=0x9FFF if even => self.bank_select = val,
+            0x8000..=0x9FFF => self.banks[(self.bank_select & 0x07) as usize] = val,
+            // Four-screen boards ignore the mirroring register
+            0xA000..=0xBFFF if even && !self.four_screen => {
+                self.mirroring = if val & 0x01 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+            }
+            0xA000..=0xBFFF if even => {}
+            0xA000..=0xBFFF => {
+                self.ram_enabled = val & 0x80 != 0;
+                self.ram_protected = val & 0x40 != 0;
+            }
+            0xC000..=0xDFFF if even => self.irq_latch = val,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xE000..=0xFFFF if even => {
+                self.irq_enabled = false;
+                self.irq = false;
+            }
+            0xE000..=0xFFFF => self.irq_enabled = true,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.watch_a12(addr);
+        self.cart.chr[self.chr_offset(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        self.watch_a12(addr);
+
+        if self.cart.chr_ram {
+            let offset = self.chr_offset(addr);
+            self.cart.chr[offset] = val;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn cartridge(&self) -> Option<&Cartridge> {
+        Some(&self.cart)
+    }
+
+    fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        Some(&mut self.cart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::cart;
+
+    // One scanline worth of A12 activity: background from $0000,
+    // sprites from $1000
+    fn scanline(mmc3: &mut Mmc3) {
+        mmc3.ppu_read(0x0000);
+        mmc3.ppu_read(0x1000);
+    }
+
+    #[test]
+    fn prg_banks_switch() {
+        let mut mmc3 = Mmc3::new(cart(4, 0x20000, 0x10000));
+
+        // R6 = 3, R7 = 5
+        mmc3.cpu_write(0x8000, 0x06);
+        mmc3.cpu_write(0x8001, 0x03);
+        mmc3.cpu_write(0x8000, 0x07);
+        mmc3.cpu_write(0x8001, 0x05);
+
+        assert_eq!(mmc3.cpu_read(0x8000), 3);
+        assert_eq!(mmc3.cpu_read(0xA000), 5);
+        assert_eq!(mmc3.cpu_read(0xC000), 14);
+        assert_eq!(mmc3.cpu_read(0xE000), 15);
+
+        // PRG mode 1 swaps $8000 and $C000
+        mmc3.cpu_write(0x8000, 0x40);
+        assert_eq!(mmc3.cpu_read(0x8000), 14);
+        assert_eq!(mmc3.cpu_read(0xC000), 3);
+        assert_eq!(mmc3.cpu_read(0xE000), 15);
+    }
+
+    #[test]
+    fn chr_banks_switch() {
+        let mut mmc3 = Mmc3::new(cart(4, 0x20000, 0x10000));
+
+        // R0 = 4 (2KB, the low bit is ignored), R2 = 9
+        mmc3.cpu_write(0x8000, 0x00);
+        mmc3.cpu_write(0x8001, 0x05);
+        mmc3.cpu_write(0x8000, 0x02);
+        mmc3.cpu_write(0x8001, 0x09);
+
+        assert_eq!(mmc3.ppu_read(0x0000), 4);
+        assert_eq!(mmc3.ppu_read(0x0400), 5);
+        assert_eq!(mmc3.ppu_read(0x1000), 9);
+
+        // A12 inversion swaps the halves
+        mmc3.cpu_write(0x8000, 0x80);
+        assert_eq!(mmc3.ppu_read(0x1000), 4);
+        assert_eq!(mmc3.ppu_read(0x1400), 5);
+        assert_eq!(mmc3.ppu_read(0x0000), 9);
+    }
+
+    #[test]
+    fn mirroring_switches() {
+        let mut mmc3 = Mmc3::new(cart(4, 0x8000, 0x2000));
+
+        mmc3.cpu_write(0xA000, 0x00);
+        assert_eq!(mmc3.mirroring(), Mirroring::Vertical);
+        mmc3.cpu_write(0xA000, 0x01);
+        assert_eq!(mmc3.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn irq_fires_after_the_programmed_scanlines() {
+        let mut mmc3 = Mmc3::new(cart(4, 0x8000, 0x2000));
+
+        // Latch 2, reload, enable
+        mmc3.cpu_write(0xC000, 0x02);
+        mmc3.cpu_write(0xC001, 0x00);
+        mmc3.cpu_write(0xE001, 0x00);
+
+        // The first clock reloads the counter, two more count it down
+        for _ in 0..2 {
+            scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+
+        // The line stays up until acknowledged
+        scanline(&mut mmc3);
+        assert!(mmc3.irq());
+        mmc3.cpu_write(0xE000, 0x00);
+        assert!(!mmc3.irq());
+
+        // Disabled, the counter keeps running without raising the line
+        for _ in 0..6 {
+            scanline(&mut mmc3);
+        }
+        assert!(!mmc3.irq());
+
+        // A12 held high does not clock the counter
+        mmc3.cpu_write(0xC001, 0x00);
+        mmc3.cpu_write(0xE001, 0x00);
+        for _ in 0..6 {
+            mmc3.ppu_read(0x1000);
+        }
+        assert!(!mmc3.irq());
+    }
+}
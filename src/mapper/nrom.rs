@@ -0,0 +1,121 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use super::Mapper;
+
+// NROM (Mapper 0)
+// No bank switching. 16KB of PRG ROM is mirrored into both
+// $8000-$BFFF and $C000-$FFFF, 32KB is mapped straight through.
+// 8KB of CHR ROM, or CHR RAM when the cartridge has none.
+// Battery backed boards also have PRG RAM at $6000-$7FFF.
+pub struct Nrom {
+    cart: Cartridge,
+}
+
+impl Nrom {
+    pub fn new(cart: Cartridge) -> Self {
+        Self { cart }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.cart.battery => self.cart.prg_ram[(addr & 0x1FFF) as usize],
+            0x8000..=0xFFFF => {
+                let offset = (addr - 0x8000) as usize % self.cart.prg_rom.len();
+                self.cart.prg_rom[offset]
+            }
+            _ => 0,
+        }
+    }
+
+    // PRG ROM can't be written
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.cart.battery => self.cart.prg_ram[(addr & 0x1FFF) as usize] = val,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.cart.chr[(addr & 0x1FFF) as usize]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.cart.chr_ram {
+            self.cart.chr[(addr & 0x1FFF) as usize] = val;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cart.mirroring
+    }
+
+    fn cartridge(&self) -> Option<&Cartridge> {
+        Some(&self.cart)
+    }
+
+    fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        Some(&mut self.cart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::cart;
+
+    #[test]
+    fn mirrors_16kb_of_prg_rom() {
+        let mut nrom = Nrom::new(cart(0, 0x4000, 0x2000));
+
+        assert_eq!(nrom.cpu_read(0x8000), 0);
+        assert_eq!(nrom.cpu_read(0xBFFF), 1);
+        assert_eq!(nrom.cpu_read(0xC000), 0);
+        assert_eq!(nrom.cpu_read(0xFFFF), 1);
+    }
+
+    #[test]
+    fn maps_32kb_of_prg_rom_straight_through() {
+        let mut nrom = Nrom::new(cart(0, 0x8000, 0x2000));
+
+        assert_eq!(nrom.cpu_read(0x8000), 0);
+        assert_eq!(nrom.cpu_read(0xA000), 1);
+        assert_eq!(nrom.cpu_read(0xC000), 2);
+        assert_eq!(nrom.cpu_read(0xFFFF), 3);
+
+        // PRG ROM ignores writes
+        nrom.cpu_write(0xC000, 0xFF);
+        assert_eq!(nrom.cpu_read(0xC000), 2);
+    }
+
+    #[test]
+    fn exposes_chr_rom() {
+        let mut nrom = Nrom::new(cart(0, 0x4000, 0x2000));
+
+        assert_eq!(nrom.ppu_read(0x0000), 0);
+        assert_eq!(nrom.ppu_read(0x1FFF), 7);
+
+        nrom.ppu_write(0x1FFF, 0xFF);
+        assert_eq!(nrom.ppu_read(0x1FFF), 7);
+    }
+
+    #[test]
+    fn battery_ram_survives_a_save_and_load() {
+        let mut battery = cart(0, 0x4000, 0x2000);
+        battery.battery = true;
+
+        let mut nrom = Nrom::new(battery);
+        nrom.cpu_write(0x6000, 0x42);
+        nrom.cpu_write(0x7FFF, 0x24);
+        let save = nrom.cartridge().unwrap().save_ram().to_vec();
+        assert_eq!(save.len(), 0x2000);
+
+        let mut fresh = cart(0, 0x4000, 0x2000);
+        fresh.battery = true;
+        fresh.load_ram(&save);
+
+        let mut nrom = Nrom::new(fresh);
+        assert_eq!(nrom.cpu_read(0x6000), 0x42);
+        assert_eq!(nrom.cpu_read(0x7FFF), 0x24);
+    }
+}
@@ -0,0 +1,858 @@
+use crate::cartridge::Mirroring;
+use crate::mapper::Mapper;
+
+// PPU registers as seen by the CPU.
+// They are mirrored every 8 bytes through $3FFF.
+const PPUCTRL: u16 = 0x2000;   // Write only
+const PPUMASK: u16 = 0x2001;   // Write only
+const PPUSTATUS: u16 = 0x2002; // Read only
+const OAMADDR: u16 = 0x2003;   // Write only
+const OAMDATA: u16 = 0x2004;   // Read / Write
+const PPUSCROLL: u16 = 0x2005; // Write only (twice)
+const PPUADDR: u16 = 0x2006;   // Write only (twice)
+const PPUDATA: u16 = 0x2007;   // Read / Write
+
+// A sprite selected for the scanline being drawn
+#[derive(Clone, Copy)]
+struct Sprite {
+    x: u8,    // Left edge on screen
+    attr: u8, // VHP...PP: flip vertical, flip horizontal, behind background, palette
+    lo: u8,   // Pattern row low bit plane, already flipped horizontally
+    hi: u8,   // Pattern row high bit plane, already flipped horizontally
+}
+
+impl Sprite {
+    // Pixel of the sprite at the given x, 0 when transparent or not covered
+    fn pixel(&self, x: u16) -> u8 {
+        let dx = x.wrapping_sub(self.x as u16);
+        if dx >= 8 {
+            return 0;
+        }
+
+        let bit = 0x80 >> dx;
+        (((self.hi & bit != 0) as u8) << 1) | (self.lo & bit != 0) as u8
+    }
+}
+
+// Struct of the NES PPU (Ricoh 2C02)
+// v and t are the 15-bit "loopy" registers:
+// yyy NN YYYYY XXXXX
+// ||| || ||||| +++++-- Coarse X scroll
+// ||| || +++++-------- Coarse Y scroll
+// ||| ++-------------- Nametable select
+// +++----------------- Fine Y scroll
+pub struct Ppu {
+    ctrl: u8,          // PPUCTRL
+    mask: u8,          // PPUMASK
+    status: u8,        // PPUSTATUS
+    oam_addr: u8,      // OAMADDR
+    v: u16,            // Current VRAM address
+    t: u16,            // Temporary VRAM address, the top left of the screen
+    fine_x: u8,        // Fine X scroll (3 bits)
+    w: bool,           // Write toggle shared by PPUSCROLL and PPUADDR
+    read_buffer: u8,   // PPUDATA read buffer
+    io_latch: u8,      // Last value driven on the CPU facing data bus
+    vram: [u8; 0x0800], // 2KB of nametable RAM
+    palette: [u8; 32], // Palette RAM
+    oam: [u8; 256],    // Object Attribute Memory, 64 sprites of 4 bytes
+    scanline: u16,     // 0-239 visible, 240 post-render, 241-260 vblank, 261 pre-render
+    cycle: u16,        // Dot within the scanline, 0-340
+
+    // Background fetch latches, loaded into the shifters every 8 dots
+    bg_next_tile: u8,
+    bg_next_attr: u8,
+    bg_next_lo: u8,
+    bg_next_hi: u8,
+
+    // Background shifters. The high byte holds the tile being drawn,
+    // the low byte the next one. Attributes are expanded to 8 bits per tile.
+    bg_pattern_lo: u16,
+    bg_pattern_hi: u16,
+    bg_attr_lo: u16,
+    bg_attr_hi: u16,
+
+    sprites: [Sprite; 8], // Up to 8 sprites on the scanline being drawn
+    sprite_count: usize,
+    sprite_zero: bool,    // Sprite 0 is in the first slot
+
+    pub framebuffer: [u8; 256 * 240], // Colour of every pixel of the frame (palette RAM values)
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Self {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_addr: 0,
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            read_buffer: 0,
+            io_latch: 0,
+            vram: [0; 0x0800],
+            palette: [0; 32],
+            oam: [0; 256],
+            scanline: 0,
+            cycle: 0,
+            bg_next_tile: 0,
+            bg_next_attr: 0,
+            bg_next_lo: 0,
+            bg_next_hi: 0,
+            bg_pattern_lo: 0,
+            bg_pattern_hi: 0,
+            bg_attr_lo: 0,
+            bg_attr_hi: 0,
+            sprites: [Sprite { x: 0, attr: 0, lo: 0, hi: 0 }; 8],
+            sprite_count: 0,
+            sprite_zero: false,
+            framebuffer: [0; 256 * 240],
+        }
+    }
+
+    // Advance the PPU by one dot.
+    // Rendering follows the timing of the hardware: during dots 1-256 of
+    // the visible scanlines a pixel is output every dot while the tiles
+    // for the next 8 pixels are fetched, one memory access every 2 dots:
+    // nametable byte, attribute byte, pattern low byte, pattern high byte.
+    // Dots 321-336 prefetch the first two tiles of the next scanline.
+    pub fn tick(&mut self, mapper: &mut dyn Mapper) {
+        let pre_render = self.scanline == 261;
+        let visible = self.scanline < 240;
+
+        if self.rendering() && (visible || pre_render) {
+            if (2..=257).contains(&self.cycle) || (321..=337).contains(&self.cycle) {
+                self.shift_background();
+
+                match (self.cycle - 1) % 8 {
+                    0 => {
+                        self.load_background();
+                        self.bg_next_tile = self.read(mapper, 0x2000 | (self.v & 0x0FFF));
+                    }
+                    2 => {
+                        let addr = 0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+                        let mut attr = self.read(mapper, addr);
+
+                        // Each attribute byte covers 4x4 tiles, 2 bits per 2x2 quadrant
+                        if self.v & 0x0040 != 0 {
+                            attr >>= 4;
+                        }
+                        if self.v & 0x0002 != 0 {
+                            attr >>= 2;
+                        }
+                        self.bg_next_attr = attr & 0x03;
+                    }
+                    4 => {
+                        let addr = self.bg_pattern_addr();
+                        self.bg_next_lo = self.read(mapper, addr);
+                    }
+                    6 => {
+                        let addr = self.bg_pattern_addr() + 8;
+                        self.bg_next_hi = self.read(mapper, addr);
+                    }
+                    7 => self.increment_x(),
+                    _ => {}
+                }
+            }
+
+            if self.cycle == 256 {
+                self.increment_y();
+            }
+
+            if self.cycle == 257 {
+                self.load_background();
+                self.transfer_x();
+
+                // Select and fetch the sprites of the next scanline.
+                // No sprites are drawn on the first scanline.
+                if pre_render {
+                    self.sprite_count = 0;
+                    self.sprite_zero = false;
+                } else {
+                    self.evaluate_sprites(mapper);
+                }
+            }
+
+            if pre_render && (280..=304).contains(&self.cycle) {
+                self.transfer_y();
+            }
+        }
+
+        // Vertical blank starts on the second dot after the post-render line
+        if self.scanline == 241 && self.cycle == 1 {
+            self.status |= 0x80;
+        }
+
+        // Vertical blank and sprite 0 hit end on the pre-render line
+        if pre_render && self.cycle == 1 {
+            self.status &= !0xC0;
+        }
+
+        if visible && (1..=256).contains(&self.cycle) {
+            let x = self.cycle - 1;
+            self.framebuffer[self.scanline as usize * 256 + x as usize] = self.pixel(mapper, x);
+        }
+
+        self.cycle += 1;
+        if self.cycle > 340 {
+            self.cycle = 0;
+            self.scanline += 1;
+            if self.scanline > 261 {
+                self.scanline = 0;
+            }
+        }
+    }
+
+    // Background or sprites are enabled in PPUMASK
+    fn rendering(&self) -> bool {
+        self.mask & 0x18 != 0
+    }
+
+    // Colour of the pixel at the given x of the current scanline
+    fn pixel(&mut self, mapper: &mut dyn Mapper, x: u16) -> u8 {
+        let mut pixel = 0;
+        let mut palette = 0;
+
+        // PPUMASK bit 3 shows the background, bit 1 shows it in the leftmost 8 pixels
+        if self.mask & 0x08 != 0 && (x >= 8 || self.mask & 0x02 != 0) {
+            let bit = 0x8000 >> self.fine_x;
+
+            pixel = (((self.bg_pattern_hi & bit != 0) as u8) << 1) | (self.bg_pattern_lo & bit != 0) as u8;
+            palette = (((self.bg_attr_hi & bit != 0) as u8) << 1) | (self.bg_attr_lo & bit != 0) as u8;
+        }
+
+        // PPUMASK bit 4 shows sprites, bit 2 shows them in the leftmost 8 pixels
+        if self.mask & 0x10 != 0 && (x >= 8 || self.mask & 0x04 != 0) {
+            // Sprite 0 hit: an opaque pixel of sprite 0 over an opaque
+            // background pixel. It never happens on the last pixel.
+            if self.sprite_zero && pixel != 0 && x != 255 && self.sprites[0].pixel(x) != 0 {
+                self.status |= 0x40;
+            }
+
+            // The first opaque sprite in OAM order wins
+            let sprite = self.sprites[..self.sprite_count].iter().find_map(|sprite| {
+                let pixel = sprite.pixel(x);
+                if pixel == 0 { None } else { Some((pixel, sprite.attr)) }
+            });
+
+            // Sprites behind the background only show through its transparent pixels
+            if let Some((sprite_pixel, attr)) = sprite {
+                if pixel == 0 || attr & 0x20 == 0 {
+                    pixel = sprite_pixel;
+                    palette = 0x04 | (attr & 0x03);
+                }
+            }
+        }
+
+        // Transparent pixels show the universal background colour
+        let index = if pixel == 0 { 0 } else { (palette << 2) | pixel };
+
+        self.read(mapper, 0x3F00 | index as u16) & 0x3F
+    }
+
+    // Find the first 8 sprites in OAM that cover the next scanline and
+    // fetch their patterns. Any further sprites on the line are dropped.
+    // Free slots fetch tile $FF like the hardware, which mappers watching
+    // the pattern table address lines (MMC3) rely on.
+    fn evaluate_sprites(&mut self, mapper: &mut dyn Mapper) {
+        let height = if self.ctrl & 0x20 != 0 { 16 } else { 8 };
+
+        self.sprite_count = 0;
+        self.sprite_zero = false;
+
+        for n in 0..64 {
+            let y = self.oam[n * 4] as u16;
+
+            // Sprites are drawn one line below their Y coordinate
+            let row = self.scanline.wrapping_sub(y);
+            if row >= height {
+                continue;
+            }
+
+            if self.sprite_count == 8 {
+                break;
+            }
+
+            let tile = self.oam[n * 4 + 1];
+            let attr = self.oam[n * 4 + 2];
+            let x = self.oam[n * 4 + 3];
+
+            let row = if attr & 0x80 != 0 { height - 1 - row } else { row };
+            let addr = self.sprite_pattern_addr(tile, row);
+
+            let mut lo = self.read(mapper, addr);
+            let mut hi = self.read(mapper, addr + 8);
+
+            if attr & 0x40 != 0 {
+                lo = lo.reverse_bits();
+                hi = hi.reverse_bits();
+            }
+
+            self.sprite_zero |= n == 0;
+            self.sprites[self.sprite_count] = Sprite { x, attr, lo, hi };
+            self.sprite_count += 1;
+        }
+
+        for _ in self.sprite_count..8 {
+            let addr = self.sprite_pattern_addr(0xFF, 0);
+            self.read(mapper, addr);
+            self.read(mapper, addr + 8);
+        }
+    }
+
+    // Address of a row of a sprite pattern.
+    // 8x8 sprites use the pattern table picked by PPUCTRL bit 3. 8x16 sprites
+    // take the table from bit 0 of the tile and are made of tile & $FE on
+    // top of the next tile.
+    fn sprite_pattern_addr(&self, tile: u8, row: u16) -> u16 {
+        if self.ctrl & 0x20 == 0 {
+            let table = if self.ctrl & 0x08 != 0 { 0x1000 } else { 0x0000 };
+            table + ((tile as u16) << 4) + row
+        } else {
+            let table = if tile & 0x01 != 0 { 0x1000 } else { 0x0000 };
+            let tile = (tile & 0xFE) as u16 + (row >> 3);
+            table + (tile << 4) + (row & 0x07)
+        }
+    }
+
+    // Address of the pattern of the next background tile on the current row
+    fn bg_pattern_addr(&self) -> u16 {
+        let table = if self.ctrl & 0x10 != 0 { 0x1000 } else { 0x0000 };
+        let fine_y = (self.v >> 12) & 0x07;
+
+        table + ((self.bg_next_tile as u16) << 4) + fine_y
+    }
+
+    // Move the background shifters on by one pixel
+    fn shift_background(&mut self) {
+        if self.mask & 0x08 != 0 {
+            self.bg_pattern_lo <<= 1;
+            self.bg_pattern_hi <<= 1;
+            self.bg_attr_lo <<= 1;
+            self.bg_attr_hi <<= 1;
+        }
+    }
+
+    // Load the fetched tile into the low byte of the background shifters
+    fn load_background(&mut self) {
+        self.bg_pattern_lo = (self.bg_pattern_lo & 0xFF00) | self.bg_next_lo as u16;
+        self.bg_pattern_hi = (self.bg_pattern_hi & 0xFF00) | self.bg_next_hi as u16;
+
+        let attr_lo = if self.bg_next_attr & 0x01 != 0 { 0xFF } else { 0x00 };
+        let attr_hi = if self.bg_next_attr & 0x02 != 0 { 0xFF } else { 0x00 };
+        self.bg_attr_lo = (self.bg_attr_lo & 0xFF00) | attr_lo;
+        self.bg_attr_hi = (self.bg_attr_hi & 0xFF00) | attr_hi;
+    }
+
+    // Move v to the next tile, wrapping into the horizontally adjacent nametable
+    fn increment_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    // Move v to the next pixel row, wrapping into the vertically adjacent nametable.
+    // Row 29 is the last row of tiles, rows 30 and 31 hold the attributes.
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    // Copy coarse X and the horizontal nametable bit from t to v
+    fn transfer_x(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    // Copy fine Y, coarse Y and the vertical nametable bit from t to v
+    fn transfer_y(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+
+    // Level of the NMI line. The PPU pulls it while in vertical blank
+    // as long as NMI generation is enabled (PPUCTRL bit 7).
+    pub fn nmi(&self) -> bool {
+        self.ctrl & 0x80 != 0 && self.status & 0x80 != 0
+    }
+
+    // Read a register at $2000-$2007.
+    // Write only registers return whatever was last left on the data bus.
+    pub fn read_register(&mut self, mapper: &mut dyn Mapper, addr: u16) -> u8 {
+        match 0x2000 | (addr & 0x0007) {
+            PPUSTATUS => {
+                // Only the top 3 bits are driven, the rest is stale bus
+                self.io_latch = (self.status & 0xE0) | (self.io_latch & 0x1F);
+
+                // Reading the status ends vblank and resets the write toggle
+                self.status &= !0x80;
+                self.w = false;
+            }
+            OAMDATA => self.io_latch = self.oam[self.oam_addr as usize],
+            PPUDATA => {
+                let addr = self.v & 0x3FFF;
+
+                // Reads are delayed by one through the read buffer,
+                // except palette reads which come back at once. The buffer
+                // still gets filled with the nametable byte "underneath"
+                // the palette ($3F00-$3FFF mirrors down to $2F00-$2FFF).
+                self.io_latch = if addr < 0x3F00 {
+                    let buffered = self.read_buffer;
+                    self.read_buffer = self.read(mapper, addr);
+                    buffered
+                } else {
+                    self.read_buffer = self.read(mapper, addr - 0x1000);
+                    self.read(mapper, addr)
+                };
+
+                self.increment_v();
+            }
+            _ => {}
+        }
+
+        self.io_latch
+    }
+
+    // Write a register at $2000-$2007.
+    // Writes to the read only PPUSTATUS only reach the data bus.
+    pub fn write_register(&mut self, mapper: &mut dyn Mapper, addr: u16, val: u8) {
+        self.io_latch = val;
+
+        match 0x2000 | (addr & 0x0007) {
+            PPUCTRL => {
+                self.ctrl = val;
+                // Nametable select goes to t
+                self.t = (self.t & 0xF3FF) | ((val as u16 & 0x03) << 10);
+            }
+            PPUMASK => self.mask = val,
+            OAMADDR => self.oam_addr = val,
+            OAMDATA => {
+                self.oam[self.oam_addr as usize] = val;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            PPUSCROLL => {
+                if !self.w {
+                    // First write: X scroll
+                    self.fine_x = val & 0x07;
+                    self.t = (self.t & 0xFFE0) | (val as u16 >> 3);
+                } else {
+                    // Second write: Y scroll
+                    self.t = (self.t & 0x8C1F) | ((val as u16 & 0x07) << 12) | ((val as u16 & 0xF8) << 2);
+                }
+                self.w = !self.w;
+            }
+            PPUADDR => {
+                if !self.w {
+                    // First write: high byte, bit 14 is cleared
+                    self.t = (self.t & 0x00FF) | ((val as u16 & 0x3F) << 8);
+                } else {
+                    // Second write: low byte, then t is copied to v
+                    self.t = (self.t & 0xFF00) | val as u16;
+                    self.v = self.t;
+                }
+                self.w = !self.w;
+            }
+            PPUDATA => {
+                self.write(mapper, self.v & 0x3FFF, val);
+                self.increment_v();
+            }
+            _ => {}
+        }
+    }
+
+    // PPUDATA accesses move v by 1 or by 32 (a row of tiles) per PPUCTRL bit 2
+    fn increment_v(&mut self) {
+        let step = if self.ctrl & 0x04 != 0 { 32 } else { 1 };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+
+    // Read from the PPU address space
+    fn read(&mut self, mapper: &mut dyn Mapper, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => mapper.ppu_read(addr),
+            0x2000..=0x3EFF => {
+                let offset = Self::mirror(mapper.mirroring(), addr);
+                match offset {
+                    0x0000..=0x07FF => self.vram[offset],
+                    _ => mapper.cartridge().map_or(0, |cart| cart.vram[offset - 0x0800]),
+                }
+            }
+            _ => self.palette[Self::palette_index(addr)],
+        }
+    }
+
+    // Write to the PPU address space
+    fn write(&mut self, mapper: &mut dyn Mapper, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => mapper.ppu_write(addr, val),
+            0x2000..=0x3EFF => {
+                let offset = Self::mirror(mapper.mirroring(), addr);
+                match offset {
+                    0x0000..=0x07FF => self.vram[offset] = val,
+                    _ => {
+                        if let Some(cart) = mapper.cartridge_mut() {
+                            cart.vram[offset - 0x0800] = val;
+                        }
+                    }
+                }
+            }
+            _ => self.palette[Self::palette_index(addr)] = val,
+        }
+    }
+
+    // Fold a palette address ($3F00-$3FFF) into an index of palette RAM.
+    // The 32 bytes repeat all the way up to $3FFF, and the backdrop entries
+    // of the sprite palettes ($3F10/$14/$18/$1C) are the same bytes as
+    // those of the background palettes ($3F00/$04/$08/$0C).
+    fn palette_index(addr: u16) -> usize {
+        let index = (addr & 0x001F) as usize;
+        if index & 0x13 == 0x10 {
+            index & 0x0F
+        } else {
+            index
+        }
+    }
+
+    // Fold a nametable address ($2000-$3EFF) into an offset of nametable RAM.
+    // There are four logical 1KB nametables but only 2KB of RAM, so the
+    // cartridge wires pairs of them together:
+    // Horizontal: $2000 = $2400 and $2800 = $2C00
+    // Vertical:   $2000 = $2800 and $2400 = $2C00
+    // Four-screen boards bring 2KB of their own for the last two,
+    // those come back as offsets from $0800.
+    fn mirror(mirroring: Mirroring, addr: u16) -> usize {
+        let addr = (addr & 0x0FFF) as usize;
+        let table = addr / 0x0400;
+
+        let page = match mirroring {
+            Mirroring::Horizontal => table >> 1,
+            Mirroring::Vertical => table & 0x01,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table,
+        };
+
+        page * 0x0400 + (addr & 0x03FF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::FlatRam;
+
+    // Point v at a PPU address through PPUADDR
+    fn set_addr(ppu: &mut Ppu, mapper: &mut FlatRam, addr: u16) {
+        ppu.write_register(mapper, PPUADDR, (addr >> 8) as u8);
+        ppu.write_register(mapper, PPUADDR, addr as u8);
+    }
+
+    #[test]
+    fn ppuaddr_takes_high_then_low() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+
+        ppu.write_register(&mut mapper, PPUADDR, 0x21);
+        assert!(ppu.w);
+        assert_eq!(ppu.v, 0x0000);
+
+        ppu.write_register(&mut mapper, PPUADDR, 0x08);
+        assert!(!ppu.w);
+        assert_eq!(ppu.v, 0x2108);
+
+        // The top two bits of the high byte are dropped
+        set_addr(&mut ppu, &mut mapper, 0xFF00);
+        assert_eq!(ppu.v, 0x3F00);
+    }
+
+    #[test]
+    fn ppustatus_read_resets_the_latch() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+        ppu.status = 0x80;
+
+        // A stray high byte is forgotten after PPUSTATUS is read
+        ppu.write_register(&mut mapper, PPUADDR, 0x3F);
+        assert_eq!(ppu.read_register(&mut mapper, PPUSTATUS) & 0x80, 0x80);
+        assert!(!ppu.w);
+        assert_eq!(ppu.read_register(&mut mapper, PPUSTATUS) & 0x80, 0x00);
+
+        set_addr(&mut ppu, &mut mapper, 0x2400);
+        assert_eq!(ppu.v, 0x2400);
+    }
+
+    #[test]
+    fn ppuscroll_shares_the_latch() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+
+        // X = 125, Y = 94
+        ppu.write_register(&mut mapper, PPUSCROLL, 0x7D);
+        ppu.write_register(&mut mapper, PPUSCROLL, 0x5E);
+        assert!(!ppu.w);
+        assert_eq!(ppu.fine_x, 0x05);
+        assert_eq!(ppu.t, 0x616F);
+
+        // A scroll write flips the toggle seen by PPUADDR
+        ppu.write_register(&mut mapper, PPUSCROLL, 0x00);
+        ppu.write_register(&mut mapper, PPUADDR, 0x10);
+        assert_eq!(ppu.v, 0x6110);
+    }
+
+    #[test]
+    fn ppudata_increments_v() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+
+        set_addr(&mut ppu, &mut mapper, 0x2000);
+        ppu.write_register(&mut mapper, PPUDATA, 0x11);
+        assert_eq!(ppu.v, 0x2001);
+
+        ppu.write_register(&mut mapper, PPUCTRL, 0x04);
+        ppu.write_register(&mut mapper, PPUDATA, 0x22);
+        assert_eq!(ppu.v, 0x2021);
+    }
+
+    // Run from the start of the pre-render line until the given
+    // visible scanline has been drawn
+    fn render_to(ppu: &mut Ppu, mapper: &mut FlatRam, line: u16) {
+        ppu.scanline = 261;
+        ppu.cycle = 0;
+
+        while ppu.scanline != line + 1 {
+            ppu.tick(mapper);
+        }
+    }
+
+    // Make tile 1 of the first pattern table solid colour 1
+    fn solid_tile(ppu: &mut Ppu, mapper: &mut FlatRam) {
+        for row in 0..8 {
+            ppu.write(mapper, 0x0010 + row, 0xFF);
+        }
+    }
+
+    #[test]
+    fn renders_a_solid_background_tile() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+        solid_tile(&mut ppu, &mut mapper);
+
+        // Tile 1 in the second column, palette 1 from the attributes
+        ppu.write(&mut mapper, 0x2001, 0x01);
+        ppu.write(&mut mapper, 0x23C0, 0x01);
+        ppu.write(&mut mapper, 0x3F00, 0x0F);
+        ppu.write(&mut mapper, 0x3F05, 0x21);
+        ppu.write_register(&mut mapper, PPUMASK, 0x0A);
+
+        render_to(&mut ppu, &mut mapper, 0);
+
+        let line = &ppu.framebuffer[..256];
+        assert_eq!(line[..8], [0x0F; 8]);
+        assert_eq!(line[8..16], [0x21; 8]);
+        assert!(line[16..].iter().all(|&pixel| pixel == 0x0F));
+    }
+
+    #[test]
+    fn fine_x_scrolls_the_background() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+        solid_tile(&mut ppu, &mut mapper);
+
+        ppu.write(&mut mapper, 0x2001, 0x01);
+        ppu.write(&mut mapper, 0x3F00, 0x0F);
+        ppu.write(&mut mapper, 0x3F01, 0x21);
+        ppu.write_register(&mut mapper, PPUMASK, 0x0A);
+        ppu.write_register(&mut mapper, PPUSCROLL, 0x03);
+        ppu.write_register(&mut mapper, PPUSCROLL, 0x00);
+
+        render_to(&mut ppu, &mut mapper, 0);
+
+        let line = &ppu.framebuffer[..256];
+        assert_eq!(line[..5], [0x0F; 5]);
+        assert_eq!(line[5..13], [0x21; 8]);
+        assert_eq!(line[13], 0x0F);
+    }
+
+    #[test]
+    fn sprites_flip() {
+        // (attributes, x, y of the only opaque pixel)
+        let cases = [(0x00, 16, 1), (0x40, 23, 1), (0x80, 16, 8), (0xC0, 23, 8)];
+
+        for &(attr, x, y) in cases.iter() {
+            let mut ppu = Ppu::new();
+            let mut mapper = FlatRam::new();
+
+            // Tile 2 has only its top left pixel set
+            ppu.write(&mut mapper, 0x0020, 0x80);
+            ppu.write(&mut mapper, 0x3F00, 0x0F);
+            ppu.write(&mut mapper, 0x3F11, 0x16);
+            ppu.oam[..4].copy_from_slice(&[0x00, 0x02, attr, 0x10]);
+            ppu.write_register(&mut mapper, PPUMASK, 0x1E);
+
+            render_to(&mut ppu, &mut mapper, 9);
+
+            let drawn: Vec<usize> = (0..256 * 10).filter(|&i| ppu.framebuffer[i] != 0x0F).collect();
+            assert_eq!(drawn, vec![y * 256 + x], "{:02X}", attr);
+            assert_eq!(ppu.framebuffer[y * 256 + x], 0x16);
+        }
+    }
+
+    #[test]
+    fn sprite_zero_hit_sets_on_the_first_overlap() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+        solid_tile(&mut ppu, &mut mapper);
+
+        // Solid background tile at x 16-23 of the top row, solid sprite 0
+        // at x 20-27 of lines 1-8
+        ppu.write(&mut mapper, 0x2002, 0x01);
+        ppu.oam[..4].copy_from_slice(&[0x00, 0x01, 0x00, 0x14]);
+        ppu.write_register(&mut mapper, PPUMASK, 0x1E);
+
+        ppu.scanline = 261;
+        ppu.cycle = 0;
+        while (ppu.scanline, ppu.cycle) != (1, 21) {
+            ppu.tick(&mut mapper);
+        }
+        assert_eq!(ppu.status & 0x40, 0);
+
+        // Dot 21 draws x = 20
+        ppu.tick(&mut mapper);
+        assert_eq!(ppu.status & 0x40, 0x40);
+
+        // Cleared on the second dot of the pre-render line
+        while (ppu.scanline, ppu.cycle) != (261, 2) {
+            ppu.tick(&mut mapper);
+        }
+        assert_eq!(ppu.status & 0x40, 0);
+    }
+
+    #[test]
+    fn sprite_zero_hit_skips_clipped_and_last_pixels() {
+        // (tile column, sprite x, PPUMASK)
+        let cases = [(0, 0x00, 0x18), (31, 0xFF, 0x1E)];
+
+        for &(column, x, mask) in cases.iter() {
+            let mut ppu = Ppu::new();
+            let mut mapper = FlatRam::new();
+            solid_tile(&mut ppu, &mut mapper);
+
+            ppu.write(&mut mapper, 0x2000 + column, 0x01);
+            ppu.oam[..4].copy_from_slice(&[0x00, 0x01, 0x00, x]);
+            ppu.write_register(&mut mapper, PPUMASK, mask);
+
+            render_to(&mut ppu, &mut mapper, 8);
+            assert_eq!(ppu.status & 0x40, 0, "{:02X} {:02X}", x, mask);
+        }
+    }
+
+    #[test]
+    fn nametable_mirroring() {
+        // Offsets of $2000, $2400, $2800 and $2C00
+        let cases = [
+            (Mirroring::Horizontal, [0x0000, 0x0000, 0x0400, 0x0400]),
+            (Mirroring::Vertical, [0x0000, 0x0400, 0x0000, 0x0400]),
+            (Mirroring::SingleScreenLower, [0x0000; 4]),
+            (Mirroring::SingleScreenUpper, [0x0400; 4]),
+            (Mirroring::FourScreen, [0x0000, 0x0400, 0x0800, 0x0C00]),
+        ];
+
+        for &(mirroring, offsets) in cases.iter() {
+            for (table, &offset) in offsets.iter().enumerate() {
+                let addr = 0x2000 + table as u16 * 0x0400;
+                assert_eq!(Ppu::mirror(mirroring, addr + 0x0123), offset + 0x0123, "{:?} {:04X}", mirroring, addr);
+            }
+        }
+
+        // $3000-$3EFF mirrors $2000-$2EFF
+        assert_eq!(Ppu::mirror(Mirroring::Vertical, 0x3456), Ppu::mirror(Mirroring::Vertical, 0x2456));
+    }
+
+    #[test]
+    fn horizontal_nametables_alias_through_ppudata() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+
+        set_addr(&mut ppu, &mut mapper, 0x2005);
+        ppu.write_register(&mut mapper, PPUDATA, 0x42);
+        set_addr(&mut ppu, &mut mapper, 0x2C05);
+        ppu.write_register(&mut mapper, PPUDATA, 0x24);
+
+        set_addr(&mut ppu, &mut mapper, 0x2405);
+        ppu.read_register(&mut mapper, PPUDATA);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x42);
+
+        set_addr(&mut ppu, &mut mapper, 0x2805);
+        ppu.read_register(&mut mapper, PPUDATA);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x24);
+    }
+
+    #[test]
+    fn sprite_backdrops_alias_the_background_ones() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+
+        for &(write, read) in [(0x3F10, 0x3F00), (0x3F04, 0x3F14), (0x3F18, 0x3F08), (0x3F0C, 0x3F1C)].iter() {
+            set_addr(&mut ppu, &mut mapper, write);
+            ppu.write_register(&mut mapper, PPUDATA, write as u8);
+            set_addr(&mut ppu, &mut mapper, read);
+            assert_eq!(ppu.read_register(&mut mapper, PPUDATA), write as u8, "{:04X}", read);
+        }
+
+        // Other sprite entries are separate, and all 32 bytes repeat up to $3FFF
+        set_addr(&mut ppu, &mut mapper, 0x3F11);
+        ppu.write_register(&mut mapper, PPUDATA, 0x2A);
+        set_addr(&mut ppu, &mut mapper, 0x3F01);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x00);
+        set_addr(&mut ppu, &mut mapper, 0x3FF1);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x2A);
+    }
+
+    #[test]
+    fn palette_reads_bypass_the_buffer() {
+        let mut ppu = Ppu::new();
+        let mut mapper = FlatRam::new();
+
+        // A nametable byte under the palette, at $2F05
+        set_addr(&mut ppu, &mut mapper, 0x2F05);
+        ppu.write_register(&mut mapper, PPUDATA, 0x77);
+        set_addr(&mut ppu, &mut mapper, 0x3F05);
+        ppu.write_register(&mut mapper, PPUDATA, 0x16);
+
+        // The palette comes back at once, the buffer gets the nametable byte
+        set_addr(&mut ppu, &mut mapper, 0x3F05);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x16);
+        assert_eq!(ppu.read_buffer, 0x77);
+
+        // Other reads still come one late
+        set_addr(&mut ppu, &mut mapper, 0x2F05);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x77);
+        set_addr(&mut ppu, &mut mapper, 0x2000);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x77);
+        assert_eq!(ppu.read_register(&mut mapper, PPUDATA), 0x00);
+    }
+}